@@ -1,4 +1,12 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tonic_build::compile_protos("proto/walletrpc.proto")?;
+    // node events are also delivered to webhooks as JSON, and the cli prints replies as JSON
+    let builder =
+        tonic_build::configure().type_attribute(".walletrpc", "#[derive(serde::Serialize)]");
+    builder
+        .type_attribute(
+            ".walletrpc.NodeEvent.event",
+            "#[serde(rename_all = \"snake_case\")]",
+        )
+        .compile(&["proto/walletrpc.proto"], &["proto"])?;
     Ok(())
 }
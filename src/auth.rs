@@ -0,0 +1,355 @@
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+use ldk_node::bitcoin::hashes::{sha256, Hash};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use tonic::service::Interceptor;
+use tonic::{Code, Request, Status};
+
+use crate::lightning::bytes_to_hex;
+use crate::paths::UserPaths;
+use crate::walletrpc::ErrorDetail;
+
+// home_dir/.bits-wallet/tokens.json
+// home_dir/.bits-wallet/admin.token
+
+/// Clients send their token as `authorization: Bearer <token>`.
+const AUTHORIZATION: &str = "authorization";
+
+/// What a token allows, each level includes the ones before it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Permission {
+    /// Reading balances, channels, payments, configs and events.
+    ReadOnly,
+    /// Also creating invoices and on-chain addresses to receive funds.
+    Invoice,
+    /// Everything, including spending funds and managing wallets and tokens.
+    Admin,
+}
+
+/// A token as stored on disk, only the hash of its secret is kept.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StoredToken {
+    pub id: String,
+    hash: String,
+    pub permission: Permission,
+    /// The wallets the token is limited to, all of them when empty.
+    pub wallet_names: Vec<String>,
+    pub created_at: u64,
+    /// Unix time after which the token is refused.
+    pub expires_at: Option<u64>,
+}
+
+/// The permissions of the token a request was authenticated with.
+#[derive(Debug, Clone)]
+pub struct Grant {
+    pub id: String,
+    pub permission: Permission,
+    pub wallet_names: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    /// The request carries no bearer token.
+    MissingToken,
+    /// The token is unknown or was revoked.
+    InvalidToken,
+    TokenExpired(String),
+    /// The token doesn't allow the call.
+    PermissionDenied(Permission),
+    /// The token is limited to other wallets, or the call needs all of them.
+    WalletNotAllowed(String),
+    /// No token with the given id exists.
+    TokenNotFound(String),
+    InvalidPermission(i32),
+    /// The token store could not be read or written.
+    Io(io::Error),
+    /// The token store could not be parsed or serialized.
+    InvalidStore(String),
+}
+
+impl AuthError {
+    /// Machine-readable name of the error, sent to clients in `ErrorDetail`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AuthError::MissingToken => "MissingToken",
+            AuthError::InvalidToken => "InvalidToken",
+            AuthError::TokenExpired(_) => "TokenExpired",
+            AuthError::PermissionDenied(_) => "PermissionDenied",
+            AuthError::WalletNotAllowed(_) => "WalletNotAllowed",
+            AuthError::TokenNotFound(_) => "TokenNotFound",
+            AuthError::InvalidPermission(_) => "InvalidPermission",
+            AuthError::Io(_) => "StorageFailed",
+            AuthError::InvalidStore(_) => "InvalidTokenStore",
+        }
+    }
+
+    pub fn code(&self) -> Code {
+        match self {
+            AuthError::MissingToken | AuthError::InvalidToken | AuthError::TokenExpired(_) => {
+                Code::Unauthenticated
+            }
+            AuthError::PermissionDenied(_) | AuthError::WalletNotAllowed(_) => {
+                Code::PermissionDenied
+            }
+            AuthError::TokenNotFound(_) => Code::NotFound,
+            AuthError::InvalidPermission(_) => Code::InvalidArgument,
+            AuthError::Io(_) | AuthError::InvalidStore(_) => Code::Internal,
+        }
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuthError::MissingToken => write!(f, "Missing bearer token"),
+            AuthError::InvalidToken => write!(f, "Invalid token"),
+            AuthError::TokenExpired(id) => write!(f, "Token {} has expired", id),
+            AuthError::PermissionDenied(required) => {
+                write!(f, "Token lacks the {:?} permission", required)
+            }
+            AuthError::WalletNotAllowed(scope) => {
+                write!(f, "Token is not allowed to access {}", scope)
+            }
+            AuthError::TokenNotFound(id) => write!(f, "Token {} not found", id),
+            AuthError::InvalidPermission(p) => write!(f, "Invalid permission: {}", p),
+            AuthError::Io(e) => write!(f, "Token storage failed: {}", e),
+            AuthError::InvalidStore(e) => write!(f, "Invalid token store: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+impl From<AuthError> for Status {
+    fn from(e: AuthError) -> Self {
+        let detail = ErrorDetail {
+            kind: e.kind().to_string(),
+        };
+        Status::with_details(e.code(), e.to_string(), detail.encode_to_vec().into())
+    }
+}
+
+impl From<io::Error> for AuthError {
+    fn from(e: io::Error) -> Self {
+        AuthError::Io(e)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn random_hex(len: usize) -> String {
+    let bytes: Vec<u8> = (0..len).map(|_| rand::random()).collect();
+    bytes_to_hex(&bytes)
+}
+
+fn hash(token: &str) -> String {
+    sha256::Hash::hash(token.as_bytes()).to_string()
+}
+
+/// Replaces a file with one only readable by its owner.
+fn write_private(path: &str, contents: &str) -> io::Result<()> {
+    let tmp_path = format!("{}.tmp", path);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp_path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(tmp_path, path)
+}
+
+impl Grant {
+    /// Checks that the token has at least `permission`, on `wallet_name` if given or on
+    /// every wallet otherwise.
+    pub fn require(
+        &self,
+        permission: Permission,
+        wallet_name: Option<&str>,
+    ) -> Result<(), AuthError> {
+        if self.permission < permission {
+            return Err(AuthError::PermissionDenied(permission));
+        }
+        match wallet_name {
+            Some(name) if !self.allows_wallet(name) => {
+                Err(AuthError::WalletNotAllowed(format!("wallet {}", name)))
+            }
+            None if !self.wallet_names.is_empty() => {
+                Err(AuthError::WalletNotAllowed("every wallet".to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn allows_wallet(&self, wallet_name: &str) -> bool {
+        self.wallet_names.is_empty() || self.wallet_names.iter().any(|w| w == wallet_name)
+    }
+}
+
+/// Returns the grant the `Authenticator` attached to the request.
+pub fn grant<T>(request: &Request<T>) -> Result<Grant, AuthError> {
+    request
+        .extensions()
+        .get::<Grant>()
+        .cloned()
+        .ok_or(AuthError::MissingToken)
+}
+
+/// Returns the grant of an authenticated request once it is checked with `Grant::require`.
+pub fn authorize<T>(
+    request: &Request<T>,
+    permission: Permission,
+    wallet_name: Option<&str>,
+) -> Result<Grant, AuthError> {
+    let grant = grant(request)?;
+    grant.require(permission, wallet_name)?;
+    Ok(grant)
+}
+
+/// Hashed tokens, kept in memory and written through to the project base dir.
+pub struct TokenStore {
+    path: String,
+    tokens: RwLock<Vec<StoredToken>>,
+}
+
+impl TokenStore {
+    /// Loads the tokens. On first run an admin token is baked and written to `admin.token`,
+    /// readable only by the owner, like lnd's `admin.macaroon`.
+    pub fn open(user_paths: &UserPaths) -> Result<Self, AuthError> {
+        let path = user_paths.tokens_file();
+        match std::fs::read(&path) {
+            Ok(bytes) => {
+                let tokens = serde_json::from_slice(&bytes)
+                    .map_err(|e| AuthError::InvalidStore(e.to_string()))?;
+                Ok(TokenStore {
+                    path,
+                    tokens: RwLock::new(tokens),
+                })
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                std::fs::create_dir_all(user_paths.project_base_dir())?;
+                let store = TokenStore {
+                    path,
+                    tokens: RwLock::new(Vec::new()),
+                };
+                let (_, token) = store.bake(Permission::Admin, Vec::new(), None)?;
+                write_private(&user_paths.admin_token_file(), &token)?;
+                println!("Baked admin token {}", user_paths.admin_token_file());
+                Ok(store)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write(&self, tokens: &[StoredToken]) -> Result<(), AuthError> {
+        let json = serde_json::to_string_pretty(tokens)
+            .map_err(|e| AuthError::InvalidStore(e.to_string()))?;
+        write_private(&self.path, &json)?;
+        Ok(())
+    }
+
+    pub fn authenticate(&self, token: &str) -> Result<Grant, AuthError> {
+        let hash = hash(token);
+        let tokens = self
+            .tokens
+            .read()
+            .map_err(|e| AuthError::InvalidStore(e.to_string()))?;
+        let token = tokens
+            .iter()
+            .find(|t| t.hash == hash)
+            .ok_or(AuthError::InvalidToken)?;
+        if token
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= now())
+        {
+            return Err(AuthError::TokenExpired(token.id.clone()));
+        }
+        Ok(Grant {
+            id: token.id.clone(),
+            permission: token.permission,
+            wallet_names: token.wallet_names.clone(),
+        })
+    }
+
+    /// Creates a token, returning its id and the secret token itself, which isn't stored.
+    pub fn bake(
+        &self,
+        permission: Permission,
+        wallet_names: Vec<String>,
+        expires_in_secs: Option<u64>,
+    ) -> Result<(String, String), AuthError> {
+        let id = random_hex(8);
+        let token = random_hex(32);
+        let created_at = now();
+        let mut tokens = self
+            .tokens
+            .write()
+            .map_err(|e| AuthError::InvalidStore(e.to_string()))?;
+        tokens.push(StoredToken {
+            id: id.clone(),
+            hash: hash(&token),
+            permission,
+            wallet_names,
+            created_at,
+            expires_at: expires_in_secs.map(|secs| created_at.saturating_add(secs)),
+        });
+        self.write(&tokens)?;
+        Ok((id, token))
+    }
+
+    pub fn revoke(&self, id: &str) -> Result<(), AuthError> {
+        let mut tokens = self
+            .tokens
+            .write()
+            .map_err(|e| AuthError::InvalidStore(e.to_string()))?;
+        let count = tokens.len();
+        tokens.retain(|t| t.id != id);
+        if tokens.len() == count {
+            return Err(AuthError::TokenNotFound(id.to_string()));
+        }
+        self.write(&tokens)
+    }
+
+    pub fn list(&self) -> Result<Vec<StoredToken>, AuthError> {
+        let tokens = self
+            .tokens
+            .read()
+            .map_err(|e| AuthError::InvalidStore(e.to_string()))?;
+        Ok(tokens.clone())
+    }
+}
+
+/// Authenticates every call before it reaches the service. The token is checked and
+/// replaced by its `Grant`, which handlers then check against what they need.
+#[derive(Clone)]
+pub struct Authenticator {
+    pub store: std::sync::Arc<TokenStore>,
+}
+
+impl Interceptor for Authenticator {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let token = request
+            .metadata()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(AuthError::MissingToken)?;
+        let grant = self.store.authenticate(token)?;
+        // keeps the token out of the request logs
+        request.metadata_mut().remove(AUTHORIZATION);
+        request.extensions_mut().insert(grant);
+        Ok(request)
+    }
+}
@@ -1,35 +1,1202 @@
-// use crate::walletrpc::{IsNodeRunningRequest, StartNodeRequest};
-// use walletrpc::wallet_rpc_service_client::WalletRpcServiceClient;
+use std::error::Error;
+use std::fmt;
+use std::io::BufRead;
 
-// pub mod walletrpc {
-//     tonic::include_proto!("walletrpc");
-// }
+use clap::{Parser, Subcommand, ValueEnum};
+use prost::Message;
+use serde::Serialize;
+use serde_json::Value;
+use tokio_stream::StreamExt;
+use tonic::Status;
+use walletrpc_tonic::paths;
+use walletrpc_tonic::wallet_client::{ClientConfig, RawClient as Client, WalletClient};
+use walletrpc_tonic::walletrpc::*;
 
-// pub async fn start_node(req: tonic::Request<StartNodeRequest>) -> Result<(), Box<dyn std::error::Error>> {
-//     let mut client = WalletRpcServiceClient::connect("http://[::1]:50051").await?;
-//     let response = client.start_node(req).await?;
+const DEFAULT_SERVER: &str = "https://[::1]:50051";
+/// Command line client of walletrpc-server
+#[derive(Parser, Debug)]
+#[command(name = "walletrpc-cli", version, about)]
+struct Args {
+    /// Server to connect to, `unix:<path>` for a Unix socket
+    #[arg(long, global = true, env = "WALLETRPC_SERVER", default_value = DEFAULT_SERVER)]
+    server: String,
+    /// Certificate of the server, `tls.cert` in the data dir by default
+    #[arg(long, global = true, env = "WALLETRPC_TLS_CERT_PATH")]
+    tls_cert: Option<String>,
+    /// Name the certificate of the server is checked against, the server host by default
+    #[arg(long, global = true, env = "WALLETRPC_TLS_DOMAIN")]
+    tls_domain: Option<String>,
+    /// Token to authenticate with, the one in `admin.token` in the data dir by default
+    #[arg(long, global = true, env = "WALLETRPC_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+    /// Directory the server keeps its files in, `~/.bits-wallet` by default
+    #[arg(long, global = true, env = "WALLETRPC_DATA_DIR")]
+    data_dir: Option<String>,
+    /// Print replies as JSON instead of tables
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
 
-//     println!("RESPONSE={:?}", response);
-//     Ok(())
-// }
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum NetworkArg {
+    Bitcoin,
+    Testnet,
+    Signet,
+    Regtest,
+}
 
-// pub async fn is_node_running(req: tonic::Request<IsNodeRunningRequest> ) -> Result<(), Box<dyn std::error::Error>> {
-//     let mut client = WalletRpcServiceClient::connect("http://[::1]:50051").await?;
-//     let response = client.is_node_running(req).await?;
+impl From<NetworkArg> for Network {
+    fn from(network: NetworkArg) -> Self {
+        match network {
+            NetworkArg::Bitcoin => Network::Bitcoin,
+            NetworkArg::Testnet => Network::Testnet,
+            NetworkArg::Signet => Network::Signet,
+            NetworkArg::Regtest => Network::Regtest,
+        }
+    }
+}
 
-//     println!("RESPONSE={:?}", response);
-//     Ok(())
-// }
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum DirectionArg {
+    Inbound,
+    Outbound,
+}
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // let mut client = WalletRpcServiceClient::connect("http://[::1]:50051").await?;
-    // let req = tonic::Request::new(StartNodeRequest {
-    //     node_name: "oip".into(),
-    // });
-    // let response = client.start_node(req).await?;
+impl From<DirectionArg> for PaymentDirection {
+    fn from(direction: DirectionArg) -> Self {
+        match direction {
+            DirectionArg::Inbound => PaymentDirection::Inbound,
+            DirectionArg::Outbound => PaymentDirection::Outbound,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum StatusArg {
+    Pending,
+    Succeeded,
+    Failed,
+}
+
+impl From<StatusArg> for PaymentStatus {
+    fn from(status: StatusArg) -> Self {
+        match status {
+            StatusArg::Pending => PaymentStatus::Pending,
+            StatusArg::Succeeded => PaymentStatus::Succeeded,
+            StatusArg::Failed => PaymentStatus::Failed,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum PermissionArg {
+    Readonly,
+    Invoice,
+    Admin,
+}
+
+impl From<PermissionArg> for Permission {
+    fn from(permission: PermissionArg) -> Self {
+        match permission {
+            PermissionArg::Readonly => Permission::ReadOnly,
+            PermissionArg::Invoice => Permission::Invoice,
+            PermissionArg::Admin => Permission::Admin,
+        }
+    }
+}
+
+/// Named like the RPCs, lowercased.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Create a wallet, printing its mnemonic
+    #[command(name = "newwallet")]
+    NewWallet {
+        wallet_name: String,
+        #[arg(long, value_enum)]
+        network: NetworkArg,
+        /// Address the node listens on for peers, like 0.0.0.0:9735
+        #[arg(long)]
+        listening_address: String,
+        /// Esplora server, the default of the server for the network when not given
+        #[arg(long, default_value = "")]
+        esplora_address: String,
+        /// Required to create a wallet on the bitcoin network
+        #[arg(long)]
+        allow_mainnet: bool,
+        /// BIP39 passphrase, not stored, it is needed to restore the wallet
+        #[arg(long, default_value = "")]
+        passphrase: String,
+        /// Password the seed is encrypted with, read from stdin when not given
+        #[arg(long, env = "WALLETRPC_WALLET_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+    },
+    /// Restore a wallet from its mnemonic and start its node
+    #[command(name = "restorewallet")]
+    RestoreWallet {
+        wallet_name: String,
+        #[arg(long, value_enum)]
+        network: NetworkArg,
+        #[arg(long)]
+        listening_address: String,
+        #[arg(long, default_value = "")]
+        esplora_address: String,
+        #[arg(long)]
+        allow_mainnet: bool,
+        /// Read from stdin when not given, to keep it out of the shell history
+        #[arg(long)]
+        mnemonic: Option<String>,
+        #[arg(long, default_value = "")]
+        passphrase: String,
+        /// Password the seed is encrypted with, read from stdin when not given
+        #[arg(long, env = "WALLETRPC_WALLET_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+    },
+    /// Decrypt the seed of a wallet so that its node can be started
+    #[command(name = "unlockwallet")]
+    UnlockWallet {
+        wallet_name: String,
+        /// Password the seed is encrypted with, read from stdin when not given
+        #[arg(long, env = "WALLETRPC_WALLET_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+    },
+    /// Encrypt the seed of a wallet with a new password
+    #[command(name = "changepassword")]
+    ChangePassword {
+        wallet_name: String,
+        /// The current password, read from stdin when not given
+        #[arg(long, env = "WALLETRPC_WALLET_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+        /// Read from stdin when not given
+        #[arg(long)]
+        new_password: Option<String>,
+    },
+    /// Print the mnemonic of a wallet
+    #[command(name = "exportmnemonic")]
+    ExportMnemonic {
+        wallet_name: String,
+        /// Password the seed is encrypted with, read from stdin when not given
+        #[arg(long, env = "WALLETRPC_WALLET_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+    },
+    #[command(name = "listwallets")]
+    ListWallets,
+    #[command(name = "getwalletconfig")]
+    GetWalletConfig { wallet_name: String },
+    /// Change the config of a wallet, taking effect the next time its node starts
+    #[command(name = "updatewalletconfig")]
+    UpdateWalletConfig {
+        wallet_name: String,
+        #[arg(long)]
+        listening_address: String,
+        #[arg(long)]
+        esplora_address: String,
+        /// Days journaled events are kept, 0 keeps them forever
+        #[arg(long)]
+        event_retention_days: Option<u32>,
+    },
+    /// Archive the wallet of a stopped node
+    #[command(name = "deletewallet")]
+    DeleteWallet {
+        wallet_name: String,
+        /// Also archive wallets with channels or on-chain funds
+        #[arg(long)]
+        force: bool,
+    },
+    /// Add a webhook, printing the secret its deliveries are signed with
+    #[command(name = "addwebhook")]
+    AddWebhook { wallet_name: String, url: String },
+    #[command(name = "removewebhook")]
+    RemoveWebhook { wallet_name: String, url: String },
+    /// Deliver a ping to a webhook
+    #[command(name = "testwebhook")]
+    TestWebhook { wallet_name: String, url: String },
+    #[command(name = "startnode")]
+    StartNode { node_name: String },
+    #[command(name = "stopnode")]
+    StopNode { node_name: String },
+    /// Stop the node if it is running and start it again, picking up config changes
+    #[command(name = "restartnode")]
+    RestartNode { node_name: String },
+    #[command(name = "isnoderunning")]
+    IsNodeRunning { node_name: String },
+    #[command(name = "getnodeid")]
+    GetNodeId { node_name: String },
+    #[command(name = "getesploraaddress")]
+    GetEsploraAddress { node_name: String },
+    #[command(name = "getnetaddress")]
+    GetNetAddress { node_name: String },
+    #[command(name = "getonchainbalance")]
+    GetOnChainBalance { node_name: String },
+    #[command(name = "getbalances")]
+    GetBalances { node_name: String },
+    #[command(name = "getonchainaddress")]
+    GetOnChainAddress { node_name: String },
+    #[command(name = "openchannel")]
+    OpenChannel {
+        node_name: String,
+        /// Node id of the counterparty
+        node_id: String,
+        /// Address of the counterparty, like 127.0.0.1:9735
+        net_address: String,
+        channel_amount_sats: i64,
+        #[arg(long, default_value_t = 0)]
+        push_to_counterparty_msat: i64,
+        #[arg(long)]
+        announce_channel: bool,
+    },
+    #[command(name = "closechannel")]
+    CloseChannel {
+        node_name: String,
+        /// As printed by listchannels
+        channel_id: String,
+        /// Node id of the counterparty
+        node_id: String,
+    },
+    #[command(name = "listchannels")]
+    ListChannels { node_name: String },
+    #[command(name = "connecttopeer")]
+    ConnectToPeer {
+        node_name: String,
+        node_id: String,
+        net_address: String,
+    },
+    #[command(name = "disconnectpeer")]
+    DisconnectPeer { node_name: String, node_id: String },
+    #[command(name = "listpeers")]
+    ListPeers { node_name: String },
+    #[command(name = "payinvoice")]
+    PayInvoice { node_name: String, invoice: String },
+    #[command(name = "createinvoice")]
+    CreateInvoice {
+        node_name: String,
+        amount_msat: i64,
+        #[arg(long, default_value = "")]
+        description: String,
+        #[arg(long, default_value_t = 3600)]
+        expiry_secs: i32,
+    },
+    #[command(name = "sendtoaddress")]
+    SendToAddress {
+        node_name: String,
+        address: String,
+        amount_sats: u64,
+    },
+    /// Send the whole spendable on-chain balance to an address
+    #[command(name = "sweepall")]
+    SweepAll { node_name: String, address: String },
+    #[command(name = "listpayments")]
+    ListPayments {
+        node_name: String,
+        #[arg(long, value_enum)]
+        direction: Option<DirectionArg>,
+        #[arg(long, value_enum)]
+        status: Option<StatusArg>,
+        /// Only payments at or after this Unix time
+        #[arg(long)]
+        since: Option<u64>,
+        /// Only payments before this Unix time
+        #[arg(long)]
+        until: Option<u64>,
+        #[arg(long, default_value_t = 0)]
+        offset: u32,
+        /// 0 lists all of them
+        #[arg(long, default_value_t = 0)]
+        limit: u32,
+    },
+    #[command(name = "getpayment")]
+    GetPayment {
+        node_name: String,
+        payment_hash: String,
+    },
+    /// Print the events of a started node as they happen, until interrupted
+    #[command(name = "subscribeevents")]
+    SubscribeEvents { node_name: String },
+    /// Print the journaled events after a sequence number
+    #[command(name = "replayevents")]
+    ReplayEvents {
+        node_name: String,
+        #[arg(long, default_value_t = 0)]
+        after_seq: u64,
+        /// 0 prints all of them
+        #[arg(long, default_value_t = 0)]
+        limit: u32,
+    },
+    /// Create a token, printing it once
+    #[command(name = "baketoken")]
+    BakeToken {
+        #[arg(value_enum)]
+        permission: PermissionArg,
+        /// Wallets the token is limited to, all of them when none are given
+        #[arg(long = "wallet")]
+        wallet_names: Vec<String>,
+        /// Seconds after which the token expires, 0 never expires
+        #[arg(long, default_value_t = 0)]
+        expires_in_secs: u64,
+    },
+    #[command(name = "revoketoken")]
+    RevokeToken { id: String },
+    #[command(name = "listtokens")]
+    ListTokens,
+}
+
+async fn connect(args: &Args) -> Result<Client, Box<dyn Error>> {
+    let user_paths = paths::UserPaths::new()?;
+    let token = match &args.token {
+        Some(token) => Some(token.clone()),
+        None => std::fs::read_to_string(user_paths.admin_token_file())
+            .ok()
+            .map(|token| token.trim().to_string()),
+    };
+    let mut config = ClientConfig::new(args.server.clone());
+    if let Some(token) = token {
+        config = config.token(token);
+    }
+    if args.server.starts_with("https://") {
+        let cert_file = args
+            .tls_cert
+            .clone()
+            .unwrap_or_else(|| user_paths.tls_cert_file());
+        let cert = std::fs::read(&cert_file)
+            .map_err(|e| format!("Unable to read {}: {}", cert_file, e))?;
+        config = config.tls_ca_certificate(cert);
+    }
+    if let Some(domain) = &args.tls_domain {
+        config = config.tls_domain(domain.clone());
+    }
+    Ok(WalletClient::connect(config).await?.raw())
+}
+
+/// Columns aligned under a header, or name/value rows when the header is empty.
+struct Table {
+    header: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    fn new(header: Vec<&'static str>) -> Self {
+        Table {
+            header,
+            rows: Vec::new(),
+        }
+    }
+
+    fn fields(fields: Vec<(&str, String)>) -> Self {
+        Table {
+            header: Vec::new(),
+            rows: fields
+                .into_iter()
+                .map(|(name, value)| vec![format!("{}:", name), value])
+                .collect(),
+        }
+    }
+
+    fn row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let header: Vec<String> = self.header.iter().map(|h| h.to_uppercase()).collect();
+        let lines: Vec<&Vec<String>> = std::iter::once(&header)
+            .filter(|h| !h.is_empty())
+            .chain(self.rows.iter())
+            .collect();
+        let columns = lines.iter().map(|l| l.len()).max().unwrap_or_default();
+        let widths: Vec<usize> = (0..columns)
+            .map(|i| {
+                lines
+                    .iter()
+                    .filter_map(|l| l.get(i))
+                    .map(|cell| cell.chars().count())
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+        for line in lines {
+            let cells: Vec<String> = line
+                .iter()
+                .enumerate()
+                .map(|(i, cell)| format!("{:width$}", cell, width = widths[i]))
+                .collect();
+            writeln!(f, "{}", cells.join("  ").trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// How a reply is printed.
+trait Render: Serialize {
+    fn table(&self) -> Table;
+
+    fn json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+}
+
+fn output<T: Render>(reply: T, json: bool) {
+    if json {
+        println!("{:#}", reply.json());
+    } else {
+        print!("{}", reply.table());
+    }
+}
+
+/// Replaces the enum value of `field` with its name, as in the proto JSON mapping.
+fn enum_name(value: &mut Value, field: &str, name: impl Fn(i32) -> Option<&'static str>) {
+    if let Some(field) = value.get_mut(field) {
+        if let Some(name) = field.as_i64().and_then(|v| name(v as i32)) {
+            *field = Value::from(name);
+        }
+    }
+}
+
+fn network_name(network: i32) -> Option<&'static str> {
+    Network::try_from(network).ok().map(|n| n.as_str_name())
+}
+
+fn payment_json(payment: &Payment) -> Value {
+    let mut value = serde_json::to_value(payment).unwrap_or_default();
+    enum_name(&mut value, "direction", |d| {
+        PaymentDirection::try_from(d).ok().map(|d| d.as_str_name())
+    });
+    enum_name(&mut value, "status", |s| {
+        PaymentStatus::try_from(s).ok().map(|s| s.as_str_name())
+    });
+    value
+}
+
+fn token_json(token: &TokenInfo) -> Value {
+    let mut value = serde_json::to_value(token).unwrap_or_default();
+    enum_name(&mut value, "permission", |p| {
+        Permission::try_from(p).ok().map(|p| p.as_str_name())
+    });
+    value
+}
+
+fn payment_row(payment: &Payment) -> Vec<String> {
+    vec![
+        payment.hash.clone(),
+        payment.direction().as_str_name().to_string(),
+        payment.status().as_str_name().to_string(),
+        payment
+            .amount_msat
+            .map(|a| a.to_string())
+            .unwrap_or_default(),
+        payment.timestamp.map(|t| t.to_string()).unwrap_or_default(),
+    ]
+}
+
+fn event_row(event: &NodeEvent) -> Vec<String> {
+    let (name, details) = match &event.event {
+        Some(node_event::Event::PaymentReceived(e)) => (
+            "payment_received",
+            format!("{} {} msat", e.payment_hash, e.amount_msat),
+        ),
+        Some(node_event::Event::PaymentSuccessful(e)) => {
+            ("payment_successful", e.payment_hash.clone())
+        }
+        Some(node_event::Event::PaymentFailed(e)) => ("payment_failed", e.payment_hash.clone()),
+        Some(node_event::Event::ChannelPending(e)) => (
+            "channel_pending",
+            format!("{} {}", e.channel_id, e.funding_txo),
+        ),
+        Some(node_event::Event::ChannelReady(e)) => ("channel_ready", e.channel_id.clone()),
+        Some(node_event::Event::ChannelClosed(e)) => ("channel_closed", e.channel_id.clone()),
+        None => ("ping", String::new()),
+    };
+    vec![
+        event.seq.to_string(),
+        event.timestamp.to_string(),
+        name.to_string(),
+        details,
+    ]
+}
+
+const EVENT_HEADER: [&str; 4] = ["seq", "timestamp", "event", "details"];
+
+impl Render for GeneralSuccessReply {
+    fn table(&self) -> Table {
+        Table::fields(vec![("success", self.success.to_string())])
+    }
+}
+
+impl Render for NewWalletReply {
+    fn table(&self) -> Table {
+        Table::fields(vec![("mnemonic", self.mnemonic.clone())])
+    }
+}
+
+impl Render for ExportMnemonicReply {
+    fn table(&self) -> Table {
+        Table::fields(vec![("mnemonic", self.mnemonic.clone())])
+    }
+}
+
+impl Render for StartNodeReply {
+    fn table(&self) -> Table {
+        let mut fields = vec![("success", self.success.to_string())];
+        if !self.msg.is_empty() {
+            fields.push(("msg", self.msg.clone()));
+        }
+        Table::fields(fields)
+    }
+}
+
+impl Render for ListWalletsReply {
+    fn table(&self) -> Table {
+        let mut table = Table::new(vec!["wallet_name"]);
+        for name in &self.wallet_names {
+            table.row(vec![name.clone()]);
+        }
+        table
+    }
+}
+
+impl Render for GetWalletConfigReply {
+    fn table(&self) -> Table {
+        Table::fields(vec![
+            ("wallet_name", self.wallet_name.clone()),
+            ("network", self.network().as_str_name().to_string()),
+            ("listening_address", self.listening_address.clone()),
+            ("esplora_address", self.esplora_address.clone()),
+            (
+                "event_retention_days",
+                self.event_retention_days.to_string(),
+            ),
+            ("webhook_urls", self.webhook_urls.join(", ")),
+        ])
+    }
+
+    fn json(&self) -> Value {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        enum_name(&mut value, "network", network_name);
+        value
+    }
+}
+
+impl Render for DeleteWalletReply {
+    fn table(&self) -> Table {
+        Table::fields(vec![("archive_dir", self.archive_dir.clone())])
+    }
+}
+
+impl Render for AddWebhookReply {
+    fn table(&self) -> Table {
+        Table::fields(vec![("secret", self.secret.clone())])
+    }
+}
+
+impl Render for RestoreWalletReply {
+    fn table(&self) -> Table {
+        Table::fields(vec![("node_id", self.node_id.clone())])
+    }
+}
+
+impl Render for GetNodeIdReply {
+    fn table(&self) -> Table {
+        Table::fields(vec![("node_id", self.node_id.clone())])
+    }
+}
+
+impl Render for GetEsploraAddressReply {
+    fn table(&self) -> Table {
+        Table::fields(vec![("address", self.address.clone())])
+    }
+}
 
-    // println!("RESPONSE={:?}", response);
+impl Render for GetNetAddressReply {
+    fn table(&self) -> Table {
+        Table::fields(vec![("address", self.address.clone())])
+    }
+}
+
+impl Render for GetOnChainBalanceReply {
+    fn table(&self) -> Table {
+        Table::fields(vec![("balance", self.balance.to_string())])
+    }
+}
+
+impl Render for GetOnChainAddressReply {
+    fn table(&self) -> Table {
+        Table::fields(vec![("address", self.address.clone())])
+    }
+}
+
+impl Render for GetBalancesReply {
+    fn table(&self) -> Table {
+        let mut table = Table::new(vec!["channel_id", "balance_msat", "outbound", "inbound"]);
+        table.row(vec![
+            "on-chain total (sats)".to_string(),
+            self.total_onchain_balance_sats.to_string(),
+        ]);
+        table.row(vec![
+            "on-chain spendable (sats)".to_string(),
+            self.spendable_onchain_balance_sats.to_string(),
+        ]);
+        table.row(vec![
+            "lightning total".to_string(),
+            self.total_lightning_balance_msat.to_string(),
+            self.outbound_capacity_msat.to_string(),
+            self.inbound_capacity_msat.to_string(),
+        ]);
+        for channel in &self.channels {
+            table.row(vec![
+                channel.channel_id.clone(),
+                channel.balance_msat.to_string(),
+                channel.outbound_capacity_msat.to_string(),
+                channel.inbound_capacity_msat.to_string(),
+            ]);
+        }
+        table
+    }
+}
+
+impl Render for ListChannelsReply {
+    fn table(&self) -> Table {
+        let mut table = Table::new(vec![
+            "channel_id",
+            "counterparty_node_id",
+            "value_sats",
+            "balance_msat",
+            "ready",
+            "usable",
+        ]);
+        let mut channels: Vec<_> = self.channels.iter().collect();
+        channels.sort_by_key(|(i, _)| **i);
+        for (_, channel) in channels {
+            table.row(vec![
+                channel.channel_id.clone(),
+                channel.counterparty_node_id.clone(),
+                channel.channel_value_sats.to_string(),
+                channel.balance_msat.to_string(),
+                channel.is_channel_ready.to_string(),
+                channel.is_usable.to_string(),
+            ]);
+        }
+        table
+    }
+}
+
+impl Render for ListPeersReply {
+    fn table(&self) -> Table {
+        let mut table = Table::new(vec!["node_id", "address", "connected", "persisted"]);
+        let mut peers: Vec<_> = self.peers.iter().collect();
+        peers.sort_by_key(|(i, _)| **i);
+        for (_, peer) in peers {
+            table.row(vec![
+                peer.node_id.clone(),
+                peer.address.clone(),
+                peer.is_connected.to_string(),
+                peer.is_persisted.to_string(),
+            ]);
+        }
+        table
+    }
+}
+
+impl Render for CreateInvoiceReply {
+    fn table(&self) -> Table {
+        Table::fields(vec![("invoice", self.invoice.clone())])
+    }
+}
 
+impl Render for SendOnChainReply {
+    fn table(&self) -> Table {
+        Table::fields(vec![("txid", self.txid.clone())])
+    }
+}
+
+impl Render for Payment {
+    fn table(&self) -> Table {
+        Table::fields(vec![
+            ("hash", self.hash.clone()),
+            ("preimage", self.preimage.clone()),
+            ("secret", self.secret.clone()),
+            (
+                "amount_msat",
+                self.amount_msat.map(|a| a.to_string()).unwrap_or_default(),
+            ),
+            ("direction", self.direction().as_str_name().to_string()),
+            ("status", self.status().as_str_name().to_string()),
+            (
+                "timestamp",
+                self.timestamp.map(|t| t.to_string()).unwrap_or_default(),
+            ),
+        ])
+    }
+
+    fn json(&self) -> Value {
+        payment_json(self)
+    }
+}
+
+impl Render for ListPaymentsReply {
+    fn table(&self) -> Table {
+        let mut table = Table::new(vec![
+            "hash",
+            "direction",
+            "status",
+            "amount_msat",
+            "timestamp",
+        ]);
+        for payment in &self.payments {
+            table.row(payment_row(payment));
+        }
+        table
+    }
+
+    fn json(&self) -> Value {
+        serde_json::json!({
+            "payments": self.payments.iter().map(payment_json).collect::<Vec<_>>(),
+            "total": self.total,
+        })
+    }
+}
+
+impl Render for NodeEvent {
+    fn table(&self) -> Table {
+        let mut table = Table::new(Vec::new());
+        table.row(event_row(self));
+        table
+    }
+}
+
+impl Render for ReplayEventsReply {
+    fn table(&self) -> Table {
+        let mut table = Table::new(EVENT_HEADER.to_vec());
+        for event in &self.events {
+            table.row(event_row(event));
+        }
+        table
+    }
+}
+
+impl Render for BakeTokenReply {
+    fn table(&self) -> Table {
+        Table::fields(vec![("id", self.id.clone()), ("token", self.token.clone())])
+    }
+}
+
+impl Render for ListTokensReply {
+    fn table(&self) -> Table {
+        let mut table = Table::new(vec![
+            "id",
+            "permission",
+            "wallet_names",
+            "created_at",
+            "expires_at",
+        ]);
+        for token in &self.tokens {
+            table.row(vec![
+                token.id.clone(),
+                token.permission().as_str_name().to_string(),
+                token.wallet_names.join(","),
+                token.created_at.to_string(),
+                match token.expires_at {
+                    0 => "never".to_string(),
+                    expires_at => expires_at.to_string(),
+                },
+            ]);
+        }
+        table
+    }
+
+    fn json(&self) -> Value {
+        serde_json::json!({
+            "tokens": self.tokens.iter().map(token_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+fn node_name(node_name: String) -> GeneralNodeNameRequest {
+    GeneralNodeNameRequest { node_name }
+}
+
+fn read_mnemonic() -> Result<String, Box<dyn Error>> {
+    eprintln!("Enter the mnemonic:");
+    let mut mnemonic = String::new();
+    std::io::stdin().lock().read_line(&mut mnemonic)?;
+    Ok(mnemonic.trim().to_string())
+}
+
+fn read_password(password: Option<String>, prompt: &str) -> Result<String, Box<dyn Error>> {
+    if let Some(password) = password {
+        return Ok(password);
+    }
+    eprintln!("{}", prompt);
+    let mut password = String::new();
+    std::io::stdin().lock().read_line(&mut password)?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
+
+async fn run(mut client: Client, command: Command, json: bool) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::NewWallet {
+            wallet_name,
+            network,
+            listening_address,
+            esplora_address,
+            allow_mainnet,
+            passphrase,
+            password,
+        } => {
+            let password = read_password(password, "Enter the wallet password:")?;
+            let request = NewWalletRequest {
+                network: Network::from(network).into(),
+                wallet_name,
+                listening_address,
+                esplora_address,
+                allow_mainnet,
+                password,
+                passphrase,
+            };
+            output(client.new_wallet(request).await?.into_inner(), json);
+        }
+        Command::RestoreWallet {
+            wallet_name,
+            network,
+            listening_address,
+            esplora_address,
+            allow_mainnet,
+            mnemonic,
+            passphrase,
+            password,
+        } => {
+            let mnemonic = match mnemonic {
+                Some(mnemonic) => mnemonic,
+                None => read_mnemonic()?,
+            };
+            let password = read_password(password, "Enter the wallet password:")?;
+            let request = RestoreWalletRequest {
+                network: Network::from(network).into(),
+                wallet_name,
+                listening_address,
+                esplora_address,
+                allow_mainnet,
+                mnemonic,
+                passphrase,
+                password,
+            };
+            output(client.restore_wallet(request).await?.into_inner(), json);
+        }
+        Command::UnlockWallet {
+            wallet_name,
+            password,
+        } => {
+            let password = read_password(password, "Enter the wallet password:")?;
+            let request = UnlockWalletRequest {
+                wallet_name,
+                password,
+            };
+            output(client.unlock_wallet(request).await?.into_inner(), json);
+        }
+        Command::ChangePassword {
+            wallet_name,
+            password,
+            new_password,
+        } => {
+            let old_password = read_password(password, "Enter the current wallet password:")?;
+            let new_password = read_password(new_password, "Enter the new wallet password:")?;
+            let request = ChangePasswordRequest {
+                wallet_name,
+                old_password,
+                new_password,
+            };
+            output(client.change_password(request).await?.into_inner(), json);
+        }
+        Command::ExportMnemonic {
+            wallet_name,
+            password,
+        } => {
+            let password = read_password(password, "Enter the wallet password:")?;
+            let request = ExportMnemonicRequest {
+                wallet_name,
+                password,
+            };
+            output(client.export_mnemonic(request).await?.into_inner(), json);
+        }
+        Command::ListWallets => {
+            let reply = client.list_wallets(ListWalletsRequest {}).await?;
+            output(reply.into_inner(), json);
+        }
+        Command::GetWalletConfig { wallet_name } => {
+            let reply = client.get_wallet_config(node_name(wallet_name)).await?;
+            output(reply.into_inner(), json);
+        }
+        Command::UpdateWalletConfig {
+            wallet_name,
+            listening_address,
+            esplora_address,
+            event_retention_days,
+        } => {
+            let request = UpdateWalletConfigRequest {
+                wallet_name,
+                listening_address,
+                esplora_address,
+                event_retention_days,
+            };
+            let reply = client.update_wallet_config(request).await?;
+            output(reply.into_inner(), json);
+        }
+        Command::DeleteWallet { wallet_name, force } => {
+            let request = DeleteWalletRequest { wallet_name, force };
+            output(client.delete_wallet(request).await?.into_inner(), json);
+        }
+        Command::AddWebhook { wallet_name, url } => {
+            let request = WebhookRequest { wallet_name, url };
+            output(client.add_webhook(request).await?.into_inner(), json);
+        }
+        Command::RemoveWebhook { wallet_name, url } => {
+            let request = WebhookRequest { wallet_name, url };
+            output(client.remove_webhook(request).await?.into_inner(), json);
+        }
+        Command::TestWebhook { wallet_name, url } => {
+            let request = WebhookRequest { wallet_name, url };
+            output(client.test_webhook(request).await?.into_inner(), json);
+        }
+        Command::StartNode { node_name: name } => {
+            output(client.start_node(node_name(name)).await?.into_inner(), json);
+        }
+        Command::StopNode { node_name: name } => {
+            output(client.stop_node(node_name(name)).await?.into_inner(), json);
+        }
+        Command::RestartNode { node_name: name } => {
+            output(
+                client.restart_node(node_name(name)).await?.into_inner(),
+                json,
+            );
+        }
+        Command::IsNodeRunning { node_name: name } => {
+            let reply = client.is_node_running(node_name(name)).await?;
+            output(reply.into_inner(), json);
+        }
+        Command::GetNodeId { node_name: name } => {
+            output(
+                client.get_node_id(node_name(name)).await?.into_inner(),
+                json,
+            );
+        }
+        Command::GetEsploraAddress { node_name: name } => {
+            let reply = client.get_esplora_address(node_name(name)).await?;
+            output(reply.into_inner(), json);
+        }
+        Command::GetNetAddress { node_name: name } => {
+            let reply = client.get_net_address(node_name(name)).await?;
+            output(reply.into_inner(), json);
+        }
+        Command::GetOnChainBalance { node_name: name } => {
+            let reply = client.get_on_chain_balance(node_name(name)).await?;
+            output(reply.into_inner(), json);
+        }
+        Command::GetBalances { node_name: name } => {
+            output(
+                client.get_balances(node_name(name)).await?.into_inner(),
+                json,
+            );
+        }
+        Command::GetOnChainAddress { node_name: name } => {
+            let reply = client.get_on_chain_address(node_name(name)).await?;
+            output(reply.into_inner(), json);
+        }
+        Command::OpenChannel {
+            node_name,
+            node_id,
+            net_address,
+            channel_amount_sats,
+            push_to_counterparty_msat,
+            announce_channel,
+        } => {
+            let request = OpenChannelRequest {
+                our_node_name: node_name,
+                node_id,
+                net_address,
+                channel_amount_sats,
+                push_to_counterparty_msat,
+                announce_channel,
+            };
+            output(client.open_channel(request).await?.into_inner(), json);
+        }
+        Command::CloseChannel {
+            node_name,
+            channel_id,
+            node_id,
+        } => {
+            let request = CloseChannelRequest {
+                our_node_name: node_name,
+                channel_id,
+                node_id,
+            };
+            output(client.close_channel(request).await?.into_inner(), json);
+        }
+        Command::ListChannels { node_name: name } => {
+            let reply = client.list_channels(node_name(name)).await?;
+            output(reply.into_inner(), json);
+        }
+        Command::ConnectToPeer {
+            node_name,
+            node_id,
+            net_address,
+        } => {
+            let request = ConnectToPeerRequest {
+                our_node_name: node_name,
+                node_id,
+                net_address,
+            };
+            output(client.connect_to_peer(request).await?.into_inner(), json);
+        }
+        Command::DisconnectPeer { node_name, node_id } => {
+            let request = DisconnectPeerRequest {
+                our_node_name: node_name,
+                node_id,
+            };
+            output(client.disconnect_peer(request).await?.into_inner(), json);
+        }
+        Command::ListPeers { node_name: name } => {
+            output(client.list_peers(node_name(name)).await?.into_inner(), json);
+        }
+        Command::PayInvoice { node_name, invoice } => {
+            let request = PayInvoiceRequest {
+                our_node_name: node_name,
+                invoice,
+            };
+            output(client.pay_invoice(request).await?.into_inner(), json);
+        }
+        Command::CreateInvoice {
+            node_name,
+            amount_msat,
+            description,
+            expiry_secs,
+        } => {
+            let request = CreateInvoiceRequest {
+                our_node_name: node_name,
+                amount_msat,
+                description,
+                expiry_secs,
+            };
+            output(client.create_invoice(request).await?.into_inner(), json);
+        }
+        Command::SendToAddress {
+            node_name,
+            address,
+            amount_sats,
+        } => {
+            let request = SendToAddressRequest {
+                our_node_name: node_name,
+                address,
+                amount_sats,
+                fee_rate_sat_per_vb: None,
+            };
+            output(client.send_to_address(request).await?.into_inner(), json);
+        }
+        Command::SweepAll { node_name, address } => {
+            let request = SweepAllRequest {
+                our_node_name: node_name,
+                address,
+            };
+            output(client.sweep_all(request).await?.into_inner(), json);
+        }
+        Command::ListPayments {
+            node_name,
+            direction,
+            status,
+            since,
+            until,
+            offset,
+            limit,
+        } => {
+            let request = ListPaymentsRequest {
+                our_node_name: node_name,
+                direction: direction.map(|d| PaymentDirection::from(d).into()),
+                status: status.map(|s| PaymentStatus::from(s).into()),
+                offset,
+                limit,
+                since,
+                until,
+            };
+            output(client.list_payments(request).await?.into_inner(), json);
+        }
+        Command::GetPayment {
+            node_name,
+            payment_hash,
+        } => {
+            let request = GetPaymentRequest {
+                our_node_name: node_name,
+                payment_hash,
+            };
+            output(client.get_payment(request).await?.into_inner(), json);
+        }
+        Command::SubscribeEvents { node_name: name } => {
+            let mut events = client.subscribe_events(node_name(name)).await?.into_inner();
+            if !json {
+                print!("{}", Table::new(EVENT_HEADER.to_vec()));
+            }
+            while let Some(event) = events.next().await {
+                let event = event?;
+                match json {
+                    true => println!("{}", serde_json::to_string(&event)?),
+                    false => print!("{}", event.table()),
+                }
+            }
+        }
+        Command::ReplayEvents {
+            node_name,
+            after_seq,
+            limit,
+        } => {
+            let request = ReplayEventsRequest {
+                node_name,
+                after_seq,
+                limit,
+            };
+            output(client.replay_events(request).await?.into_inner(), json);
+        }
+        Command::BakeToken {
+            permission,
+            wallet_names,
+            expires_in_secs,
+        } => {
+            let request = BakeTokenRequest {
+                permission: Permission::from(permission).into(),
+                wallet_names,
+                expires_in_secs,
+            };
+            output(client.bake_token(request).await?.into_inner(), json);
+        }
+        Command::RevokeToken { id } => {
+            let request = RevokeTokenRequest { id };
+            output(client.revoke_token(request).await?.into_inner(), json);
+        }
+        Command::ListTokens => {
+            let reply = client.list_tokens(ListTokensRequest {}).await?;
+            output(reply.into_inner(), json);
+        }
+    }
     Ok(())
 }
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    if let Some(data_dir) = args.data_dir.clone() {
+        paths::set_project_base_dir(data_dir);
+    }
+    let result = match connect(&args).await {
+        Ok(client) => run(client, args.command, args.json).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        match e.downcast_ref::<Status>() {
+            Some(status) => {
+                let kind = ErrorDetail::decode(status.details())
+                    .map(|detail| detail.kind)
+                    .unwrap_or_default();
+                eprintln!("{:?} {}: {}", status.code(), kind, status.message());
+            }
+            None => eprintln!("{}", e),
+        }
+        std::process::exit(1);
+    }
+}
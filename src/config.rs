@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use clap::Parser;
+use ldk_node::bitcoin::Network;
+use ldk_node::LogLevel;
+use serde::Deserialize;
+
+use crate::paths::UserPaths;
+use crate::tls;
+
+// home_dir/.bits-wallet/walletrpc.toml
+// home_dir/.bits-wallet/walletrpc.sock
+
+const CONFIG_FILE_NAME: &str = "walletrpc.toml";
+const DEFAULT_LISTEN_ADDRESS: &str = "[::1]:50051";
+/// Prefix of listen addresses that are Unix socket paths.
+const UNIX_PREFIX: &str = "unix:";
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+
+/// Command line flags, each also settable through its environment variable. They take
+/// precedence over the config file.
+#[derive(Parser, Debug)]
+#[command(name = "walletrpc-server", version, about)]
+struct Args {
+    /// Config file to read, `walletrpc.toml` in the data dir by default
+    #[arg(long, env = "WALLETRPC_CONFIG")]
+    config: Option<String>,
+    /// Address to serve on, `unix:<path>` for a Unix socket served without TLS, can be
+    /// repeated
+    #[arg(long, env = "WALLETRPC_LISTEN", value_delimiter = ',')]
+    listen: Vec<String>,
+    /// Serve on `walletrpc.sock` in the project base dir, and on TCP only when listen
+    /// addresses are given
+    #[arg(long, env = "WALLETRPC_UNIX_SOCKET")]
+    unix_socket: bool,
+    /// Directory holding wallets, tokens and TLS files, instead of `~/.bits-wallet`
+    #[arg(long, env = "WALLETRPC_DATA_DIR")]
+    data_dir: Option<String>,
+    /// Level of the node logs: gossip, trace, debug, info, warn or error
+    #[arg(long, env = "WALLETRPC_LOG_LEVEL")]
+    log_level: Option<String>,
+    /// Esplora server of new wallets as `<network>=<url>`, can be repeated
+    #[arg(long, env = "WALLETRPC_ESPLORA_ADDRESS", value_delimiter = ',')]
+    esplora_address: Vec<String>,
+    /// Rapid gossip sync server as `<network>=<url>`, an empty url uses p2p gossip
+    #[arg(long, env = "WALLETRPC_RGS_URL", value_delimiter = ',')]
+    rgs_url: Vec<String>,
+    /// PEM certificate to serve instead of the generated one
+    #[arg(long, env = tls::CERT_PATH_VAR)]
+    tls_cert_path: Option<String>,
+    /// PEM private key of the certificate
+    #[arg(long, env = tls::KEY_PATH_VAR)]
+    tls_key_path: Option<String>,
+    /// PEM CA certificate, clients must then present a certificate it signed
+    #[arg(long, env = tls::CLIENT_CA_PATH_VAR)]
+    tls_client_ca_path: Option<String>,
+    /// Seconds to wait for the nodes to stop on SIGINT or SIGTERM before exiting anyway,
+    /// 30 by default
+    #[arg(long, env = "WALLETRPC_SHUTDOWN_TIMEOUT")]
+    shutdown_timeout: Option<u64>,
+}
+
+/// The config file, every field is optional.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    listen: Vec<String>,
+    unix_socket: bool,
+    data_dir: Option<String>,
+    log_level: Option<String>,
+    /// Keyed by network name, like `regtest`.
+    esplora_addresses: HashMap<String, String>,
+    rgs_urls: HashMap<String, String>,
+    tls: TlsPaths,
+    /// In seconds.
+    shutdown_timeout: Option<u64>,
+}
+
+/// Files of the TLS identity, the generated ones are used when unset.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsPaths {
+    pub cert_path: Option<String>,
+    pub key_path: Option<String>,
+    pub client_ca_path: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenAddress {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl FromStr for ListenAddress {
+    type Err = ConfigError;
+
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = address.strip_prefix(UNIX_PREFIX) {
+            if path.is_empty() {
+                return Err(ConfigError::InvalidListenAddress(address.to_string()));
+            }
+            return Ok(ListenAddress::Unix(PathBuf::from(path)));
+        }
+        address
+            .parse()
+            .map(ListenAddress::Tcp)
+            .map_err(|_| ConfigError::InvalidListenAddress(address.to_string()))
+    }
+}
+
+impl fmt::Display for ListenAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ListenAddress::Tcp(address) => write!(f, "{}", address),
+            ListenAddress::Unix(path) => write!(f, "{}{}", UNIX_PREFIX, path.display()),
+        }
+    }
+}
+
+/// Server-wide settings of the nodes, per-wallet settings live in `WalletConfig`.
+#[derive(Debug, Clone)]
+pub struct NodeDefaults {
+    pub log_level: LogLevel,
+    esplora_addresses: HashMap<Network, String>,
+    rgs_urls: HashMap<Network, String>,
+}
+
+impl Default for NodeDefaults {
+    /// Info logs, no esplora servers and the public rapid gossip sync servers. Regtest and
+    /// signet have none, they fall back to p2p gossip.
+    fn default() -> Self {
+        NodeDefaults {
+            log_level: LogLevel::Info,
+            esplora_addresses: HashMap::new(),
+            rgs_urls: HashMap::from([
+                (
+                    Network::Bitcoin,
+                    "https://rapidsync.lightningdevkit.org/snapshot".to_string(),
+                ),
+                (
+                    Network::Testnet,
+                    "https://rapidsync.lightningdevkit.org/testnet/snapshot".to_string(),
+                ),
+            ]),
+        }
+    }
+}
+
+impl NodeDefaults {
+    pub fn set_esplora_address(&mut self, network: Network, esplora_address: String) {
+        self.esplora_addresses.insert(network, esplora_address);
+    }
+
+    /// An empty url uses p2p gossip.
+    pub fn set_rgs_url(&mut self, network: Network, rgs_url: String) {
+        self.rgs_urls.insert(network, rgs_url);
+    }
+
+    /// The esplora server of new wallets that don't name one.
+    pub fn esplora_address(&self, network: Network) -> Option<&str> {
+        self.esplora_addresses.get(&network).map(|a| a.as_str())
+    }
+
+    /// The rapid gossip sync server of the network, `None` to use p2p gossip.
+    pub fn rgs_url(&self, network: Network) -> Option<&str> {
+        self.rgs_urls
+            .get(&network)
+            .map(|u| u.as_str())
+            .filter(|u| !u.is_empty())
+    }
+}
+
+#[derive(Debug)]
+pub struct ServerConfig {
+    pub listen: Vec<ListenAddress>,
+    /// Whether to also serve on the socket of the project base dir.
+    pub unix_socket: bool,
+    pub data_dir: Option<String>,
+    pub node_defaults: NodeDefaults,
+    pub tls: TlsPaths,
+    /// How long stopping the nodes and finishing the requests under way may take on
+    /// shutdown. Stopping a node dead-letters its pending webhook deliveries within a few
+    /// seconds, so this only runs out on requests or nodes that hang.
+    pub shutdown_timeout: Duration,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(String, io::Error),
+    Parse(String, toml::de::Error),
+    InvalidListenAddress(String),
+    InvalidLogLevel(String),
+    InvalidNetwork(String),
+    InvalidUrl(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "Unable to read config {}: {}", path, e),
+            ConfigError::Parse(path, e) => write!(f, "Invalid config {}: {}", path, e),
+            ConfigError::InvalidListenAddress(address) => {
+                write!(f, "Invalid listen address: {:?}", address)
+            }
+            ConfigError::InvalidLogLevel(level) => write!(f, "Invalid log level: {:?}", level),
+            ConfigError::InvalidNetwork(network) => write!(f, "Invalid network: {:?}", network),
+            ConfigError::InvalidUrl(url) => write!(f, "Invalid url: {:?}", url),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn parse_log_level(level: &str) -> Result<LogLevel, ConfigError> {
+    match level.to_lowercase().as_str() {
+        "gossip" => Ok(LogLevel::Gossip),
+        "trace" => Ok(LogLevel::Trace),
+        "debug" => Ok(LogLevel::Debug),
+        "info" => Ok(LogLevel::Info),
+        "warn" => Ok(LogLevel::Warn),
+        "error" => Ok(LogLevel::Error),
+        _ => Err(ConfigError::InvalidLogLevel(level.to_string())),
+    }
+}
+
+/// Splits a `<network>=<url>` flag.
+fn parse_network_url(flag: &str) -> Result<(String, String), ConfigError> {
+    flag.split_once('=')
+        .map(|(network, url)| (network.to_string(), url.to_string()))
+        .ok_or_else(|| ConfigError::InvalidUrl(flag.to_string()))
+}
+
+/// Keys the urls by network, checking that they are http urls unless `allow_empty`.
+fn network_urls(
+    urls: HashMap<String, String>,
+    allow_empty: bool,
+) -> Result<HashMap<Network, String>, ConfigError> {
+    urls.into_iter()
+        .map(|(network, url)| {
+            let network = Network::from_str(&network)
+                .map_err(|_| ConfigError::InvalidNetwork(network.clone()))?;
+            let is_http_url = url.starts_with("http://") || url.starts_with("https://");
+            if !(is_http_url || allow_empty && url.is_empty()) {
+                return Err(ConfigError::InvalidUrl(url));
+            }
+            Ok((network, url))
+        })
+        .collect()
+}
+
+impl ServerConfig {
+    /// Reads the config file and applies the flags and environment variables over it. The
+    /// config file is looked up in the data dir given by flag, or else in the project base
+    /// dir.
+    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        let args = Args::parse();
+        let file = match &args.config {
+            Some(path) => read_file(path)?,
+            None => {
+                let data_dir = match &args.data_dir {
+                    Some(data_dir) => data_dir.clone(),
+                    None => UserPaths::new()?.project_base_dir(),
+                };
+                let path = format!("{}/{}", data_dir, CONFIG_FILE_NAME);
+                match read_file(&path) {
+                    Err(ConfigError::Io(_, e)) if e.kind() == io::ErrorKind::NotFound => {
+                        FileConfig::default()
+                    }
+                    result => result?,
+                }
+            }
+        };
+        Ok(ServerConfig::merge(args, file)?)
+    }
+
+    fn merge(args: Args, file: FileConfig) -> Result<Self, ConfigError> {
+        let unix_socket = args.unix_socket || file.unix_socket;
+        let listen = match (args.listen.is_empty(), file.listen.is_empty()) {
+            (false, _) => args.listen,
+            (true, false) => file.listen,
+            (true, true) if unix_socket => Vec::new(),
+            (true, true) => vec![DEFAULT_LISTEN_ADDRESS.to_string()],
+        };
+        let listen = listen
+            .iter()
+            .map(|address| address.parse())
+            .collect::<Result<_, _>>()?;
+        let log_level = match args.log_level.or(file.log_level) {
+            Some(level) => parse_log_level(&level)?,
+            None => LogLevel::Info,
+        };
+        let mut esplora_addresses = file.esplora_addresses;
+        for flag in &args.esplora_address {
+            let (network, url) = parse_network_url(flag)?;
+            esplora_addresses.insert(network, url);
+        }
+        let mut rgs_urls = file.rgs_urls;
+        for flag in &args.rgs_url {
+            let (network, url) = parse_network_url(flag)?;
+            rgs_urls.insert(network, url);
+        }
+        let mut node_defaults = NodeDefaults {
+            log_level,
+            esplora_addresses: network_urls(esplora_addresses, false)?,
+            ..NodeDefaults::default()
+        };
+        node_defaults.rgs_urls.extend(network_urls(rgs_urls, true)?);
+        Ok(ServerConfig {
+            listen,
+            unix_socket,
+            data_dir: args.data_dir.or(file.data_dir),
+            node_defaults,
+            tls: TlsPaths {
+                cert_path: args.tls_cert_path.or(file.tls.cert_path),
+                key_path: args.tls_key_path.or(file.tls.key_path),
+                client_ca_path: args.tls_client_ca_path.or(file.tls.client_ca_path),
+            },
+            shutdown_timeout: Duration::from_secs(
+                args.shutdown_timeout
+                    .or(file.shutdown_timeout)
+                    .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_SECS),
+            ),
+        })
+    }
+}
+
+fn read_file(path: &str) -> Result<FileConfig, ConfigError> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_string(), e))?;
+    toml::from_str(&contents).map_err(|e| ConfigError::Parse(path.to_string(), e))
+}
@@ -0,0 +1,205 @@
+//! Conversions between the types of the core and the generated gRPC ones.
+
+use ldk_node::bitcoin::Network;
+use ldk_node::{PaymentDirection, PaymentStatus};
+
+use crate::auth::{self, Permission};
+use crate::lightning::{
+    self, Balances, ChanDetails, LightningError, WrappedPaymentDetails, WrappedPeerDetails,
+};
+use crate::wallet::WalletError;
+use crate::walletrpc::{self, Channel, ChannelBalance, GetBalancesReply, Payment, Peer, TokenInfo};
+
+impl From<ChanDetails> for Channel {
+    fn from(chan_details: ChanDetails) -> Self {
+        Channel {
+            channel_id: chan_details.channel_id.to_string(),
+            user_channel_id: lightning::user_channel_id_to_hex(chan_details.user_channel_id),
+            counterparty_node_id: chan_details.counterparty_node_id.to_string(),
+            channel_value_sats: chan_details.channel_value_sats as i64,
+            balance_msat: chan_details.balance_msat as i64,
+            outbound_capacity_msat: chan_details.outbound_capacity_msat as i64,
+            inbound_capacity_msat: chan_details.inbound_capacity_msat as i64,
+            is_outbound: chan_details.is_outbound,
+            is_channel_ready: chan_details.is_channel_ready,
+            is_usable: chan_details.is_usable,
+            is_public: chan_details.is_public,
+        }
+    }
+}
+
+impl From<ChanDetails> for ChannelBalance {
+    fn from(chan_details: ChanDetails) -> Self {
+        ChannelBalance {
+            channel_id: chan_details.channel_id.to_string(),
+            counterparty_node_id: chan_details.counterparty_node_id.to_string(),
+            balance_msat: chan_details.balance_msat,
+            outbound_capacity_msat: chan_details.outbound_capacity_msat,
+            inbound_capacity_msat: chan_details.inbound_capacity_msat,
+            is_usable: chan_details.is_usable,
+        }
+    }
+}
+
+impl From<Balances> for GetBalancesReply {
+    fn from(balances: Balances) -> Self {
+        GetBalancesReply {
+            total_onchain_balance_sats: balances.total_onchain_balance_sats,
+            spendable_onchain_balance_sats: balances.spendable_onchain_balance_sats,
+            total_lightning_balance_msat: balances.total_lightning_balance_msat,
+            outbound_capacity_msat: balances.outbound_capacity_msat,
+            inbound_capacity_msat: balances.inbound_capacity_msat,
+            channels: balances.channels.into_iter().map(|c| c.into()).collect(),
+        }
+    }
+}
+
+impl TryFrom<Channel> for ChanDetails {
+    type Error = LightningError;
+
+    fn try_from(channel: Channel) -> Result<Self, Self::Error> {
+        Ok(ChanDetails {
+            channel_id: lightning::parse_channel_id(&channel.channel_id)?,
+            user_channel_id: lightning::parse_user_channel_id(&channel.user_channel_id)?,
+            counterparty_node_id: lightning::parse_public_key(&channel.counterparty_node_id)?,
+            channel_value_sats: channel.channel_value_sats as u64,
+            balance_msat: channel.balance_msat as u64,
+            outbound_capacity_msat: channel.outbound_capacity_msat as u64,
+            inbound_capacity_msat: channel.inbound_capacity_msat as u64,
+            is_outbound: channel.is_outbound,
+            is_channel_ready: channel.is_channel_ready,
+            is_usable: channel.is_usable,
+            is_public: channel.is_public,
+        })
+    }
+}
+
+impl From<walletrpc::Network> for Network {
+    fn from(network: walletrpc::Network) -> Self {
+        match network {
+            walletrpc::Network::Testnet => Network::Testnet,
+            walletrpc::Network::Regtest => Network::Regtest,
+            walletrpc::Network::Signet => Network::Signet,
+            walletrpc::Network::Bitcoin => Network::Bitcoin,
+        }
+    }
+}
+
+impl TryFrom<Network> for walletrpc::Network {
+    type Error = WalletError;
+
+    fn try_from(network: Network) -> Result<Self, Self::Error> {
+        match network {
+            Network::Testnet => Ok(walletrpc::Network::Testnet),
+            Network::Regtest => Ok(walletrpc::Network::Regtest),
+            Network::Signet => Ok(walletrpc::Network::Signet),
+            Network::Bitcoin => Ok(walletrpc::Network::Bitcoin),
+            _ => Err(WalletError::InvalidNetwork(network.to_string())),
+        }
+    }
+}
+
+impl From<walletrpc::Permission> for Permission {
+    fn from(permission: walletrpc::Permission) -> Self {
+        match permission {
+            walletrpc::Permission::ReadOnly => Permission::ReadOnly,
+            walletrpc::Permission::Invoice => Permission::Invoice,
+            walletrpc::Permission::Admin => Permission::Admin,
+        }
+    }
+}
+
+impl From<Permission> for walletrpc::Permission {
+    fn from(permission: Permission) -> Self {
+        match permission {
+            Permission::ReadOnly => walletrpc::Permission::ReadOnly,
+            Permission::Invoice => walletrpc::Permission::Invoice,
+            Permission::Admin => walletrpc::Permission::Admin,
+        }
+    }
+}
+
+impl From<auth::StoredToken> for TokenInfo {
+    fn from(token: auth::StoredToken) -> Self {
+        let permission: walletrpc::Permission = token.permission.into();
+        TokenInfo {
+            id: token.id,
+            permission: permission.into(),
+            wallet_names: token.wallet_names,
+            created_at: token.created_at,
+            expires_at: token.expires_at.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<WrappedPeerDetails> for Peer {
+    fn from(peer_details: WrappedPeerDetails) -> Self {
+        Peer {
+            address: peer_details.address,
+            node_id: peer_details.node_id.to_string(),
+            is_persisted: peer_details.is_persisted,
+            is_connected: peer_details.is_connected,
+            alias: peer_details.alias,
+        }
+    }
+}
+
+impl From<walletrpc::PaymentDirection> for PaymentDirection {
+    fn from(direction: walletrpc::PaymentDirection) -> Self {
+        match direction {
+            walletrpc::PaymentDirection::Inbound => PaymentDirection::Inbound,
+            walletrpc::PaymentDirection::Outbound => PaymentDirection::Outbound,
+        }
+    }
+}
+
+impl From<PaymentDirection> for walletrpc::PaymentDirection {
+    fn from(direction: PaymentDirection) -> Self {
+        match direction {
+            PaymentDirection::Inbound => walletrpc::PaymentDirection::Inbound,
+            PaymentDirection::Outbound => walletrpc::PaymentDirection::Outbound,
+        }
+    }
+}
+
+impl From<walletrpc::PaymentStatus> for PaymentStatus {
+    fn from(status: walletrpc::PaymentStatus) -> Self {
+        match status {
+            walletrpc::PaymentStatus::Pending => PaymentStatus::Pending,
+            walletrpc::PaymentStatus::Succeeded => PaymentStatus::Succeeded,
+            walletrpc::PaymentStatus::Failed => PaymentStatus::Failed,
+        }
+    }
+}
+
+impl From<PaymentStatus> for walletrpc::PaymentStatus {
+    fn from(status: PaymentStatus) -> Self {
+        match status {
+            PaymentStatus::Pending => walletrpc::PaymentStatus::Pending,
+            PaymentStatus::Succeeded => walletrpc::PaymentStatus::Succeeded,
+            PaymentStatus::Failed => walletrpc::PaymentStatus::Failed,
+        }
+    }
+}
+
+impl From<WrappedPaymentDetails> for Payment {
+    fn from(payment_details: WrappedPaymentDetails) -> Self {
+        let direction: walletrpc::PaymentDirection = payment_details.direction.into();
+        let status: walletrpc::PaymentStatus = payment_details.status.into();
+        Payment {
+            hash: lightning::bytes_to_hex(&payment_details.hash),
+            preimage: payment_details
+                .preimage
+                .map(|p| lightning::bytes_to_hex(&p))
+                .unwrap_or_default(),
+            secret: payment_details
+                .secret
+                .map(|s| lightning::bytes_to_hex(&s))
+                .unwrap_or_default(),
+            amount_msat: payment_details.amount_msat.map(|a| a as i64),
+            direction: direction.into(),
+            status: status.into(),
+            timestamp: payment_details.timestamp,
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use prost::bytes::Buf;
+use prost::Message;
+
+use crate::walletrpc::NodeEvent;
+
+// home_dir/.bits-wallet/wallets/wallet_name/ldk-data/events.journal
+
+const JOURNAL_FILE: &str = "events.journal";
+
+/// Append-only journal of the events of one node, stored as length-delimited `NodeEvent`s.
+/// Every event gets the next sequence number, starting at 1, so clients can replay
+/// whatever they missed from the last sequence number they saw.
+pub struct EventJournal {
+    path: PathBuf,
+    next_seq: u64,
+}
+
+fn journal_path(ldk_data_dir: &str) -> PathBuf {
+    PathBuf::from(ldk_data_dir).join(JOURNAL_FILE)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Reads every event of the journal, oldest first. A missing journal is empty, and a
+/// record cut short by a crash while it was being appended is ignored.
+pub fn read_events(ldk_data_dir: &str) -> io::Result<Vec<NodeEvent>> {
+    let bytes = match std::fs::read(journal_path(ldk_data_dir)) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut buf = bytes.as_slice();
+    let mut events = Vec::new();
+    while buf.has_remaining() {
+        match NodeEvent::decode_length_delimited(&mut buf) {
+            Ok(event) => events.push(event),
+            Err(_) => break,
+        }
+    }
+    Ok(events)
+}
+
+impl EventJournal {
+    /// Opens the journal of a node, first dropping events older than `retention_days`.
+    /// A retention of 0 keeps every event. The newest event is always kept so that
+    /// sequence numbers carry on from where they were.
+    pub fn open(ldk_data_dir: &str, retention_days: u32) -> io::Result<Self> {
+        let path = journal_path(ldk_data_dir);
+        let mut events = read_events(ldk_data_dir)?;
+        let next_seq = events.last().map_or(1, |e| e.seq + 1);
+        if retention_days > 0 {
+            let cutoff = now().saturating_sub(u64::from(retention_days) * 24 * 60 * 60);
+            let newest = events.pop();
+            events.retain(|e| e.timestamp >= cutoff);
+            events.extend(newest);
+        }
+        // Rewriting also drops a partially appended record, if any.
+        let tmp_path = path.with_extension("tmp");
+        let mut tmp = File::create(&tmp_path)?;
+        for event in &events {
+            tmp.write_all(&event.encode_length_delimited_to_vec())?;
+        }
+        tmp.sync_all()?;
+        std::fs::rename(tmp_path, &path)?;
+        Ok(EventJournal { path, next_seq })
+    }
+
+    /// Stamps the event with its sequence number and time, and appends it durably.
+    pub fn append(&mut self, mut event: NodeEvent) -> io::Result<NodeEvent> {
+        event.seq = self.next_seq;
+        event.timestamp = now();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)?;
+        file.write_all(&event.encode_length_delimited_to_vec())?;
+        file.sync_data()?;
+        self.next_seq += 1;
+        Ok(event)
+    }
+}
@@ -0,0 +1,22 @@
+//! The wallet node manager and its gRPC server and client. `NodeManager` runs the
+//! wallets in-process, `walletrpc-server` serves it over gRPC and
+//! `wallet_client::WalletClient` talks to that server.
+
+pub mod auth;
+pub mod config;
+mod convert;
+pub mod journal;
+pub mod lightning;
+pub mod manager;
+pub mod paths;
+pub mod tls;
+pub mod wallet;
+pub mod wallet_client;
+pub mod webhook;
+
+pub use manager::NodeManager;
+
+pub mod walletrpc {
+    // The string specified here must match the proto package name
+    tonic::include_proto!("walletrpc");
+}
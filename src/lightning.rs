@@ -1,224 +1,362 @@
 use lazy_static::lazy_static;
+use ldk_node::bitcoin::address::NetworkUnchecked;
+use ldk_node::bitcoin::hashes::hex::FromHex;
 use ldk_node::bitcoin::secp256k1::PublicKey;
+use ldk_node::bitcoin::Address;
 use ldk_node::io::sqlite_store::SqliteStore;
 use ldk_node::lightning::ln::msgs::SocketAddress;
-use ldk_node::lightning::ln::ChannelId;
+use ldk_node::lightning::ln::{ChannelId, PaymentHash};
 use ldk_node::lightning_invoice::{Bolt11Invoice, SignedRawBolt11Invoice};
 use ldk_node::{
-    Builder, ChannelDetails, LogLevel, Network, Node, PaymentDetails, PaymentDirection,
-    PaymentStatus, PeerDetails,
+    BuildError, Builder, ChannelDetails, Event, Network, Node, NodeError, PaymentDetails,
+    PaymentDirection, PaymentStatus, PeerDetails,
 };
+use prost::Message;
 use std::collections::HashMap;
+use std::fmt;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::os::unix::fs::OpenOptionsExt;
 use std::str::FromStr;
-use std::sync::{Arc, RwLock};
-use std::thread;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tonic::{Code, Status};
 
+use crate::config::NodeDefaults;
+use crate::journal::EventJournal;
 use crate::paths::UserPaths;
-use crate::wallet::WalletConfig;
+use crate::wallet::{SecretSeed, WalletError, Webhook};
+use crate::walletrpc::{self, ErrorDetail, NodeEvent};
+use crate::webhook;
 
-pub fn start_node(node_name: String) -> (bool, String) {
-    let seed = match std::fs::read(UserPaths::new().seed_file(&node_name)) {
-        Ok(s) => s,
-        Err(e) => {
-            return (false, e.to_string());
-        }
-    };
-    let config = match WalletConfig::new(&node_name) {
-        Ok(c) => c,
-        Err(e) => {
-            return (false, e.to_string());
-        }
-    };
-    dbg!(&config);
-    init_lazy(Arc::new(NodeConf {
-        network: ldk_node::bitcoin::Network::Testnet,
-        seed,
-        storage_dir: UserPaths::new().ldk_data_dir(&node_name),
-        listening_address: config.get_listening_address(),
-        esplora_address: config.get_esplora_address(),
-    }))
-}
-
-pub fn get_node_id(node_name: String) -> String {
-    let node = match NODES.read() {
-        Ok(n) => n,
-        Err(e) => {
-            dbg!(&e);
-            return "".to_string();
-        }
-    };
-    let node = match node.get(&UserPaths::new().ldk_data_dir(&node_name)) {
-        Some(n) => n,
-        None => {
-            dbg!("Unable to get node");
-            return "".to_string();
-        }
-    };
-    node.node_id().to_string()
+#[derive(Debug)]
+pub enum LightningError {
+    /// No node with the given name is running.
+    NodeNotFound(String),
+    /// The registry of running nodes could not be accessed.
+    NodesUnavailable(String),
+    /// The wallet data directory could not be located.
+    DataDirUnavailable(String),
+    /// The wallet must be unlocked with its password before its node is started.
+    WalletLocked(String),
+    /// The wallet config could not be read.
+    Wallet(WalletError),
+    InvalidPublicKey(String),
+    InvalidSocketAddress(String),
+    InvalidInvoice(String),
+    InvalidChannelId(String),
+    InvalidPaymentHash(String),
+    InvalidAddress(String),
+    /// The address belongs to another network than the wallet.
+    AddressNetworkMismatch(String),
+    /// The underlying ldk node always estimates its own fee rate.
+    FeeRateUnsupported,
+    /// A `ListPayments` filter holds an unknown direction or status.
+    InvalidPaymentFilter(String),
+    /// The node has no payment with the given hash.
+    PaymentNotFound(String),
+    /// The node must be stopped first.
+    NodeRunning(String),
+    /// The node is being started or stopped by another call.
+    NodeBusy(String),
+    /// The store of the node is locked by another process running it.
+    StoreLocked(String),
+    /// The wallet may still hold funds, in channels or on-chain.
+    WalletNotEmpty(String),
+    /// A subscriber fell behind and missed the given number of events.
+    EventsLagged(u64),
+    /// The events the replay should start from are past the retention of the journal,
+    /// the oldest event still kept has the given sequence number.
+    EventsPruned(u64),
+    /// The event journal of the node could not be read or written.
+    JournalUnavailable(String),
+    /// A test delivery to a webhook failed on every attempt.
+    WebhookDeliveryFailed(String),
+    /// The ldk node could not be built from the wallet config.
+    Build(BuildError),
+    /// An error returned by the underlying ldk node.
+    Node(NodeError),
 }
 
-pub fn stop_node(node_name: String) -> bool {
-    let node = match NODES.read() {
-        Ok(n) => n,
-        Err(e) => {
-            dbg!(&e);
-            return false;
-        }
-    };
-    let node = match node.get(&UserPaths::new().ldk_data_dir(&node_name)) {
-        Some(n) => n,
-        None => {
-            dbg!("Unable to get node");
-            return false;
+impl LightningError {
+    /// Machine-readable name of the error, sent to clients in `ErrorDetail`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LightningError::NodeNotFound(_) => "NodeNotFound",
+            LightningError::NodesUnavailable(_) => "NodesUnavailable",
+            LightningError::DataDirUnavailable(_) => "DataDirUnavailable",
+            LightningError::WalletLocked(_) => "WalletLocked",
+            LightningError::Wallet(e) => e.kind(),
+            LightningError::InvalidPublicKey(_) => "InvalidPublicKey",
+            LightningError::InvalidSocketAddress(_) => "InvalidSocketAddress",
+            LightningError::InvalidInvoice(_) => "InvalidInvoice",
+            LightningError::InvalidChannelId(_) => "InvalidChannelId",
+            LightningError::InvalidPaymentHash(_) => "InvalidPaymentHash",
+            LightningError::InvalidPaymentFilter(_) => "InvalidPaymentFilter",
+            LightningError::InvalidAddress(_) => "InvalidAddress",
+            LightningError::AddressNetworkMismatch(_) => "AddressNetworkMismatch",
+            LightningError::FeeRateUnsupported => "FeeRateUnsupported",
+            LightningError::PaymentNotFound(_) => "PaymentNotFound",
+            LightningError::NodeRunning(_) => "NodeRunning",
+            LightningError::NodeBusy(_) => "NodeBusy",
+            LightningError::StoreLocked(_) => "StoreLocked",
+            LightningError::WalletNotEmpty(_) => "WalletNotEmpty",
+            LightningError::EventsLagged(_) => "EventsLagged",
+            LightningError::EventsPruned(_) => "EventsPruned",
+            LightningError::JournalUnavailable(_) => "JournalUnavailable",
+            LightningError::WebhookDeliveryFailed(_) => "WebhookDeliveryFailed",
+            LightningError::Build(_) => "BuildFailed",
+            LightningError::Node(e) => match e {
+                NodeError::AlreadyRunning => "AlreadyRunning",
+                NodeError::NotRunning => "NotRunning",
+                NodeError::OnchainTxCreationFailed => "OnchainTxCreationFailed",
+                NodeError::ConnectionFailed => "ConnectionFailed",
+                NodeError::InvoiceCreationFailed => "InvoiceCreationFailed",
+                NodeError::PaymentSendingFailed => "PaymentSendingFailed",
+                NodeError::ProbeSendingFailed => "ProbeSendingFailed",
+                NodeError::ChannelCreationFailed => "ChannelCreationFailed",
+                NodeError::ChannelClosingFailed => "ChannelClosingFailed",
+                NodeError::ChannelConfigUpdateFailed => "ChannelConfigUpdateFailed",
+                NodeError::PersistenceFailed => "PersistenceFailed",
+                NodeError::FeerateEstimationUpdateFailed => "FeerateEstimationUpdateFailed",
+                NodeError::WalletOperationFailed => "WalletOperationFailed",
+                NodeError::OnchainTxSigningFailed => "OnchainTxSigningFailed",
+                NodeError::MessageSigningFailed => "MessageSigningFailed",
+                NodeError::TxSyncFailed => "TxSyncFailed",
+                NodeError::GossipUpdateFailed => "GossipUpdateFailed",
+                NodeError::InvalidAddress => "InvalidAddress",
+                NodeError::InvalidSocketAddress => "InvalidSocketAddress",
+                NodeError::InvalidPublicKey => "InvalidPublicKey",
+                NodeError::InvalidSecretKey => "InvalidSecretKey",
+                NodeError::InvalidPaymentHash => "InvalidPaymentHash",
+                NodeError::InvalidPaymentPreimage => "InvalidPaymentPreimage",
+                NodeError::InvalidPaymentSecret => "InvalidPaymentSecret",
+                NodeError::InvalidAmount => "InvalidAmount",
+                NodeError::InvalidInvoice => "InvalidInvoice",
+                NodeError::InvalidChannelId => "InvalidChannelId",
+                NodeError::InvalidNetwork => "InvalidNetwork",
+                NodeError::DuplicatePayment => "DuplicatePayment",
+                NodeError::InsufficientFunds => "InsufficientFunds",
+            },
         }
-    };
-    match node.stop() {
-        Ok(_) => {
-            dbg!("Node stopped");
-            return true;
+    }
+
+    pub fn code(&self) -> Code {
+        match self {
+            LightningError::NodeNotFound(_) => Code::NotFound,
+            LightningError::NodesUnavailable(_) => Code::Internal,
+            LightningError::DataDirUnavailable(_) => Code::Internal,
+            LightningError::WalletLocked(_) => Code::FailedPrecondition,
+            LightningError::Wallet(e) => e.code(),
+            LightningError::InvalidPublicKey(_)
+            | LightningError::InvalidSocketAddress(_)
+            | LightningError::InvalidInvoice(_)
+            | LightningError::InvalidChannelId(_)
+            | LightningError::InvalidPaymentHash(_)
+            | LightningError::InvalidPaymentFilter(_)
+            | LightningError::InvalidAddress(_)
+            | LightningError::AddressNetworkMismatch(_) => Code::InvalidArgument,
+            LightningError::FeeRateUnsupported => Code::Unimplemented,
+            LightningError::PaymentNotFound(_) => Code::NotFound,
+            LightningError::NodeRunning(_)
+            | LightningError::StoreLocked(_)
+            | LightningError::WalletNotEmpty(_) => Code::FailedPrecondition,
+            LightningError::NodeBusy(_) => Code::Aborted,
+            LightningError::EventsLagged(_) => Code::DataLoss,
+            LightningError::EventsPruned(_) => Code::OutOfRange,
+            LightningError::JournalUnavailable(_) => Code::Internal,
+            LightningError::WebhookDeliveryFailed(_) => Code::Unavailable,
+            LightningError::Build(_) => Code::Internal,
+            LightningError::Node(e) => match e {
+                NodeError::AlreadyRunning
+                | NodeError::NotRunning
+                | NodeError::InsufficientFunds => Code::FailedPrecondition,
+                NodeError::DuplicatePayment => Code::AlreadyExists,
+                NodeError::ConnectionFailed
+                | NodeError::TxSyncFailed
+                | NodeError::GossipUpdateFailed
+                | NodeError::FeerateEstimationUpdateFailed => Code::Unavailable,
+                NodeError::PaymentSendingFailed
+                | NodeError::ProbeSendingFailed
+                | NodeError::ChannelCreationFailed
+                | NodeError::ChannelClosingFailed
+                | NodeError::ChannelConfigUpdateFailed
+                | NodeError::OnchainTxCreationFailed => Code::Aborted,
+                NodeError::InvalidAddress
+                | NodeError::InvalidSocketAddress
+                | NodeError::InvalidPublicKey
+                | NodeError::InvalidSecretKey
+                | NodeError::InvalidPaymentHash
+                | NodeError::InvalidPaymentPreimage
+                | NodeError::InvalidPaymentSecret
+                | NodeError::InvalidAmount
+                | NodeError::InvalidInvoice
+                | NodeError::InvalidChannelId
+                | NodeError::InvalidNetwork => Code::InvalidArgument,
+                NodeError::InvoiceCreationFailed
+                | NodeError::PersistenceFailed
+                | NodeError::WalletOperationFailed
+                | NodeError::OnchainTxSigningFailed
+                | NodeError::MessageSigningFailed => Code::Internal,
+            },
         }
-        Err(_) => return false,
-    };
+    }
 }
 
-pub fn is_node_running(node_name: String) -> bool {
-    let node = match NODES.read() {
-        Ok(n) => n,
-        Err(e) => {
-            dbg!(&e);
-            return false;
-        }
-    };
-    let node = match node.get(&UserPaths::new().ldk_data_dir(&node_name)) {
-        Some(n) => n,
-        None => {
-            dbg!("Unable to get node");
-            return false;
+impl fmt::Display for LightningError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LightningError::NodeNotFound(name) => {
+                write!(f, "Node {} not found, is it started?", name)
+            }
+            LightningError::NodesUnavailable(e) => write!(f, "Unable to access nodes: {}", e),
+            LightningError::DataDirUnavailable(e) => {
+                write!(f, "Unable to locate data directory: {}", e)
+            }
+            LightningError::WalletLocked(name) => write!(f, "Wallet {} is locked", name),
+            LightningError::Wallet(e) => write!(f, "{}", e),
+            LightningError::InvalidPublicKey(e) => write!(f, "Invalid public key: {}", e),
+            LightningError::InvalidSocketAddress(e) => {
+                write!(f, "Invalid socket address: {}", e)
+            }
+            LightningError::InvalidInvoice(e) => write!(f, "Invalid invoice: {}", e),
+            LightningError::InvalidChannelId(e) => write!(f, "Invalid channel id: {}", e),
+            LightningError::InvalidPaymentHash(e) => write!(f, "Invalid payment hash: {}", e),
+            LightningError::InvalidPaymentFilter(e) => write!(f, "Invalid payment filter: {}", e),
+            LightningError::InvalidAddress(e) => write!(f, "Invalid address: {}", e),
+            LightningError::AddressNetworkMismatch(e) => {
+                write!(f, "Address is for another network: {}", e)
+            }
+            LightningError::FeeRateUnsupported => {
+                write!(f, "Custom fee rates are not supported, omit the fee rate")
+            }
+            LightningError::PaymentNotFound(hash) => write!(f, "Payment {} not found", hash),
+            LightningError::NodeRunning(name) => write!(f, "Node {} is running", name),
+            LightningError::NodeBusy(name) => {
+                write!(f, "Node {} is being started or stopped, try again", name)
+            }
+            LightningError::StoreLocked(dir) => {
+                write!(f, "Node store {} is in use by another process", dir)
+            }
+            LightningError::WalletNotEmpty(e) => write!(f, "Wallet is not empty: {}", e),
+            LightningError::EventsLagged(missed) => {
+                write!(f, "Subscriber is too slow, missed {} events", missed)
+            }
+            LightningError::EventsPruned(oldest) => {
+                write!(f, "Events were pruned, the oldest one kept is {}", oldest)
+            }
+            LightningError::JournalUnavailable(e) => {
+                write!(f, "Unable to access event journal: {}", e)
+            }
+            LightningError::WebhookDeliveryFailed(e) => {
+                write!(f, "Webhook delivery failed: {}", e)
+            }
+            LightningError::Build(e) => write!(f, "Unable to build node: {}", e),
+            LightningError::Node(e) => write!(f, "{}", e),
         }
-    };
-    node.is_running()
+    }
 }
 
-pub fn new_onchain_address(node_name: String) -> String {
-    let empty_result = "".to_string();
-    let node = match NODES.read() {
-        Ok(n) => n,
-        Err(e) => {
-            dbg!(&e);
-            return empty_result;
-        }
-    };
-    let node = match node.get(&UserPaths::new().ldk_data_dir(&node_name)) {
-        Some(n) => n,
-        None => {
-            dbg!("Unable to get node");
-            return empty_result;
-        }
-    };
-    match node.new_onchain_address() {
-        Ok(a) => a.to_string(),
-        Err(e) => {
-            dbg!(e);
-            "".to_string()
-        }
+impl std::error::Error for LightningError {}
+
+impl From<BuildError> for LightningError {
+    fn from(e: BuildError) -> Self {
+        LightningError::Build(e)
     }
 }
 
-pub fn close_channel(node_name: String, node_id: String, channel_id: ChannelId) -> bool {
-    let node = match NODES.read() {
-        Ok(n) => n,
-        Err(e) => {
-            dbg!(&e);
-            return false;
-        }
-    };
-    let node = match node.get(&UserPaths::new().ldk_data_dir(&node_name)) {
-        Some(n) => n,
-        None => {
-            dbg!("Unable to get node");
-            return false;
-        }
-    };
-    let pub_key = match PublicKey::from_str(&node_id) {
-        Ok(key) => key,
-        Err(e) => {
-            dbg!(&e);
-            return false;
-        }
-    };
-    match node.close_channel(&channel_id, pub_key) {
-        Ok(_) => return true,
-        Err(e) => {
-            dbg!(&e);
-            return false;
-        }
-    };
+impl From<WalletError> for LightningError {
+    fn from(e: WalletError) -> Self {
+        LightningError::Wallet(e)
+    }
 }
 
-pub fn open_channel(
-    node_name: String,
-    node_id: String,
-    net_address: String,
-    channel_amount_sats: u64,
-    push_to_counterparty_msat: u64,
-    announce_channel: bool,
-) -> bool {
-    let empty_result = false;
-    let node = match NODES.read() {
-        Ok(n) => n,
-        Err(e) => {
-            dbg!(&e);
-            return empty_result;
-        }
-    };
-    let node = match node.get(&UserPaths::new().ldk_data_dir(&node_name)) {
-        Some(n) => n,
-        None => {
-            dbg!("Unable to get node");
-            return empty_result;
-        }
-    };
-    let target_node_id = match PublicKey::from_str(&node_id) {
-        Ok(key) => key,
-        Err(e) => {
-            dbg!(&e);
-            return empty_result;
-        }
-    };
+impl From<NodeError> for LightningError {
+    fn from(e: NodeError) -> Self {
+        LightningError::Node(e)
+    }
+}
 
-    let target_address = match SocketAddress::from_str(&net_address) {
-        Ok(address) => address,
-        Err(e) => {
-            dbg!(&e);
-            return false;
-        }
-    };
+impl From<LightningError> for Status {
+    fn from(e: LightningError) -> Self {
+        let detail = ErrorDetail {
+            kind: e.kind().to_string(),
+        };
+        Status::with_details(e.code(), e.to_string(), detail.encode_to_vec().into())
+    }
+}
 
-    let push_to_counterparty_msat: Option<u64> = if push_to_counterparty_msat > 1 {
-        Some(push_to_counterparty_msat)
-    } else {
-        None
-    };
+pub(crate) fn user_paths() -> Result<UserPaths, LightningError> {
+    UserPaths::new().map_err(|e| LightningError::DataDirUnavailable(e.to_string()))
+}
 
-    let channel_config = None;
-    match node.connect_open_channel(
-        target_node_id,
-        target_address,
-        channel_amount_sats,
-        push_to_counterparty_msat,
-        channel_config,
-        announce_channel,
-    ) {
-        Ok(_) => true,
-        Err(e) => {
-            dbg!(&e);
-            false
-        }
+pub(crate) fn get_node(node_name: &str) -> Result<Arc<Node<SqliteStore>>, LightningError> {
+    let nodes = NODES
+        .read()
+        .map_err(|e| LightningError::NodesUnavailable(e.to_string()))?;
+    match nodes.get(&user_paths()?.ldk_data_dir(node_name)) {
+        Some(n) => Ok(n.clone()),
+        None => Err(LightningError::NodeNotFound(node_name.to_string())),
     }
 }
 
+pub fn parse_public_key(node_id: &str) -> Result<PublicKey, LightningError> {
+    PublicKey::from_str(node_id).map_err(|e| LightningError::InvalidPublicKey(e.to_string()))
+}
+
+pub fn parse_socket_address(net_address: &str) -> Result<SocketAddress, LightningError> {
+    SocketAddress::from_str(net_address)
+        .map_err(|_| LightningError::InvalidSocketAddress(net_address.to_string()))
+}
+
+/// Parses a hex encoded 32-byte channel id, as returned by `list_channels`.
+pub fn parse_channel_id(channel_id: &str) -> Result<ChannelId, LightningError> {
+    <[u8; 32]>::from_hex(channel_id)
+        .map(ChannelId)
+        .map_err(|_| LightningError::InvalidChannelId(channel_id.to_string()))
+}
+
+/// Parses a hex encoded 16-byte user channel id, as returned by `list_channels`.
+pub fn parse_user_channel_id(user_channel_id: &str) -> Result<u128, LightningError> {
+    <[u8; 16]>::from_hex(user_channel_id)
+        .map(u128::from_be_bytes)
+        .map_err(|_| LightningError::InvalidChannelId(user_channel_id.to_string()))
+}
+
+/// Parses a hex encoded 32-byte payment hash.
+pub fn parse_payment_hash(payment_hash: &str) -> Result<PaymentHash, LightningError> {
+    <[u8; 32]>::from_hex(payment_hash)
+        .map(PaymentHash)
+        .map_err(|_| LightningError::InvalidPaymentHash(payment_hash.to_string()))
+}
+
+/// Parses an on-chain address, its network is checked against the wallet's when used.
+pub fn parse_address(address: &str) -> Result<Address<NetworkUnchecked>, LightningError> {
+    Address::<NetworkUnchecked>::from_str(address)
+        .map_err(|e| LightningError::InvalidAddress(format!("{}: {}", address, e)))
+}
+
+pub fn parse_invoice(invoice: &str) -> Result<Bolt11Invoice, LightningError> {
+    let invoice = SignedRawBolt11Invoice::from_str(invoice)
+        .map_err(|e| LightningError::InvalidInvoice(e.to_string()))?;
+    Bolt11Invoice::from_signed(invoice).map_err(|e| LightningError::InvalidInvoice(e.to_string()))
+}
+
+/// Hex encodes hashes, preimages and secrets the same way `parse_payment_hash` reads them.
+pub fn bytes_to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Hex encodes a user channel id so that it can be read back by `parse_user_channel_id`.
+pub fn user_channel_id_to_hex(user_channel_id: u128) -> String {
+    format!("{:032x}", user_channel_id)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ChanDetails {
     pub channel_id: ChannelId,
+    pub user_channel_id: u128,
     pub counterparty_node_id: PublicKey,
     pub channel_value_sats: u64,
     pub balance_msat: u64,
@@ -235,6 +373,7 @@ impl From<ChannelDetails> for ChanDetails {
         ChanDetails {
             counterparty_node_id: channel_details.counterparty_node_id,
             channel_id: ChannelId(channel_details.channel_id.0),
+            user_channel_id: channel_details.user_channel_id.0,
             channel_value_sats: channel_details.channel_value_sats,
             balance_msat: channel_details.balance_msat,
             outbound_capacity_msat: channel_details.outbound_capacity_msat,
@@ -247,7 +386,7 @@ impl From<ChannelDetails> for ChanDetails {
     }
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[derive(Debug)]
 pub struct WrappedPaymentDetails {
     /// The payment hash, i.e., the hash of the `preimage`.
     pub hash: [u8; 32],
@@ -258,217 +397,27 @@ pub struct WrappedPaymentDetails {
     /// The amount transferred.
     pub amount_msat: Option<u64>,
     /// The direction of the payment.
-    pub direction: String,
+    pub direction: PaymentDirection,
     /// The status of the payment.
-    pub status: String,
+    pub status: PaymentStatus,
+    /// When the last event of the payment was journaled, unknown to the ldk node.
+    pub timestamp: Option<u64>,
 }
 
 impl From<PaymentDetails> for WrappedPaymentDetails {
     fn from(payment_details: PaymentDetails) -> Self {
-        return WrappedPaymentDetails {
+        WrappedPaymentDetails {
             hash: payment_details.hash.0,
-            preimage: match payment_details.preimage {
-                Some(p) => Some(p.0),
-                None => None,
-            },
-            secret: match payment_details.secret {
-                Some(s) => Some(s.0),
-                None => None,
-            },
+            preimage: payment_details.preimage.map(|p| p.0),
+            secret: payment_details.secret.map(|s| s.0),
             amount_msat: payment_details.amount_msat,
-            direction: match payment_details.direction {
-                PaymentDirection::Inbound => "Inbound".to_string(),
-                PaymentDirection::Outbound => "Outbound".to_string(),
-            },
-            status: match payment_details.status {
-                PaymentStatus::Pending => "Pending".to_string(),
-                PaymentStatus::Succeeded => "Succeeded".to_string(),
-                PaymentStatus::Failed => "Failed".to_string(),
-            },
-        };
-    }
-}
-
-pub fn list_payments(node_name: String) -> Vec<WrappedPaymentDetails> {
-    let node = match NODES.read() {
-        Ok(n) => n,
-        Err(e) => {
-            dbg!(&e);
-            return vec![];
-        }
-    };
-    let node = match node.get(&UserPaths::new().ldk_data_dir(&node_name)) {
-        Some(n) => n,
-        None => {
-            dbg!("Unable to get node");
-            return vec![];
-        }
-    };
-    node.list_payments()
-        .into_iter()
-        .map(|c: PaymentDetails| WrappedPaymentDetails::from(c))
-        .collect()
-}
-
-pub fn list_channels(node_name: String) -> Vec<ChanDetails> {
-    let node = match NODES.read() {
-        Ok(n) => n,
-        Err(e) => {
-            dbg!(&e);
-            return vec![];
-        }
-    };
-    let node = match node.get(&UserPaths::new().ldk_data_dir(&node_name)) {
-        Some(n) => n,
-        None => {
-            dbg!("Unable to get node");
-            return vec![];
-        }
-    };
-    node.list_channels()
-        .into_iter()
-        .map(|c: ChannelDetails| ChanDetails::from(c))
-        .collect()
-}
-
-pub fn create_invoice(
-    node_name: String,
-    amount_msat: u64,
-    description: &str,
-    expiry_secs: u32,
-) -> Option<String> {
-    let node = match NODES.read() {
-        Ok(n) => n,
-        Err(e) => {
-            dbg!(&e);
-            return None;
-        }
-    };
-    let node = match node.get(&UserPaths::new().ldk_data_dir(&node_name)) {
-        Some(n) => n,
-        None => {
-            dbg!("Unable to get node");
-            return None;
-        }
-    };
-    match node.receive_payment(amount_msat, description, expiry_secs) {
-        Ok(i) => Some(i.into_signed_raw().to_string()),
-        Err(e) => {
-            dbg!(&e);
-            None
+            direction: payment_details.direction,
+            status: payment_details.status,
+            timestamp: None,
         }
     }
 }
 
-/// returns payment hash if successful
-pub fn pay_invoice(node_name: String, invoice: String) -> Option<[u8; 32]> {
-    let node = match NODES.read() {
-        Ok(n) => n,
-        Err(e) => {
-            dbg!(&e);
-            return None;
-        }
-    };
-    let node = match node.get(&UserPaths::new().ldk_data_dir(&node_name)) {
-        Some(n) => n,
-        None => {
-            dbg!("Unable to get node");
-            return None;
-        }
-    };
-    let invoice = match SignedRawBolt11Invoice::from_str(&invoice) {
-        Ok(i) => i,
-        Err(e) => {
-            dbg!(&e);
-            return None;
-        }
-    };
-    let invoice = match Bolt11Invoice::from_signed(invoice) {
-        Ok(i) => i,
-        Err(e) => {
-            dbg!(&e);
-            return None;
-        }
-    };
-    match node.send_payment(&invoice) {
-        Ok(p) => Some(p.0),
-        Err(e) => {
-            dbg!(&e);
-            None
-        }
-    }
-}
-
-pub fn disconnect_peer(node_name: String, node_id: String) -> bool {
-    let node = match NODES.read() {
-        Ok(n) => n,
-        Err(e) => {
-            dbg!(&e);
-            return false;
-        }
-    };
-    let node = match node.get(&UserPaths::new().ldk_data_dir(&node_name)) {
-        Some(n) => n,
-        None => {
-            dbg!("Unable to get node");
-            return false;
-        }
-    };
-    let pub_key = match PublicKey::from_str(&node_id) {
-        Ok(key) => key,
-        Err(e) => {
-            dbg!(&e);
-            return false;
-        }
-    };
-    match node.disconnect(pub_key) {
-        Ok(_) => return true,
-        Err(e) => {
-            dbg!(&e);
-            return false;
-        }
-    };
-}
-
-pub fn connect_to_node(our_node_name: String, node_id: String, net_address: String) -> bool {
-    let persist = true;
-    let node = match NODES.read() {
-        Ok(n) => n,
-        Err(e) => {
-            dbg!(&e);
-            return false;
-        }
-    };
-    let node = match node.get(&UserPaths::new().ldk_data_dir(&our_node_name)) {
-        Some(n) => n,
-        None => {
-            dbg!("Unable to get node");
-            return false;
-        }
-    };
-    let pub_key = match PublicKey::from_str(&node_id) {
-        Ok(key) => key,
-        Err(e) => {
-            dbg!(&e);
-            return false;
-        }
-    };
-    let listening_address = match SocketAddress::from_str(&net_address) {
-        Ok(address) => address,
-        Err(e) => {
-            dbg!(&e);
-            return false;
-        }
-    };
-    match node.connect(pub_key, listening_address, persist) {
-        Ok(_) => return true,
-        Err(e) => {
-            dbg!(&e);
-            return false;
-        }
-    };
-}
-
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub struct WrappedPeerDetails {
     /// The node ID of the peer.
@@ -495,160 +444,381 @@ impl From<PeerDetails> for WrappedPeerDetails {
     }
 }
 
-pub fn list_peers(node_name: String) -> Vec<WrappedPeerDetails> {
-    let node = match NODES.read() {
-        Ok(n) => n,
-        Err(e) => {
-            dbg!(&e);
-            return vec![];
-        }
-    };
-    let node = match node.get(&UserPaths::new().ldk_data_dir(&node_name)) {
-        Some(n) => n,
-        None => {
-            dbg!("Unable to get node");
-            return vec![];
-        }
-    };
-    node.list_peers()
-        .into_iter()
-        .map(|peer: PeerDetails| peer.into())
-        .collect()
+/// On-chain and lightning balances of a node, gathered in one go.
+pub struct Balances {
+    pub total_onchain_balance_sats: u64,
+    pub spendable_onchain_balance_sats: u64,
+    pub total_lightning_balance_msat: u64,
+    pub outbound_capacity_msat: u64,
+    pub inbound_capacity_msat: u64,
+    pub channels: Vec<ChanDetails>,
 }
 
-pub fn spendable_on_chain(node_name: String) -> u64 {
-    let node = match NODES.read() {
-        Ok(n) => n,
-        Err(e) => {
-            dbg!(&e);
-            return 0;
-        }
-    };
-    let node = match node.get(&UserPaths::new().ldk_data_dir(&node_name)) {
-        Some(n) => n,
-        None => {
-            dbg!("Unable to get node");
-            return 0;
-        }
-    };
-    match node.spendable_onchain_balance_sats() {
-        Ok(b) => return b,
-        Err(e) => {
-            dbg!(&e);
-            return 0;
-        }
-    }
+/// Everything a node is built from. It holds the seed so it is neither `Debug` nor
+/// `Serialize`.
+pub struct NodeConf {
+    pub network: ldk_node::bitcoin::Network,
+    pub storage_dir: String,
+    pub listening_address: String,
+    pub seed: SecretSeed,
+    pub esplora_address: String,
+    pub event_retention_days: u32,
+    pub wallet_name: String,
+    pub webhooks: Vec<Webhook>,
 }
 
-pub fn total_onchain_balance(node_name: String) -> u64 {
-    let node = match NODES.read() {
-        Ok(n) => n,
-        Err(e) => {
-            dbg!(&e);
-            return 0;
-        }
-    };
-    let node = match node.get(&UserPaths::new().ldk_data_dir(&node_name)) {
-        Some(n) => n,
-        None => {
-            dbg!("Unable to get node");
-            return 0;
-        }
-    };
-    match node.total_onchain_balance_sats() {
-        Ok(b) => {
-            return b;
-        }
-        Err(e) => {
-            dbg!(&e);
-            return 0;
-        }
-    }
+/// Events a subscriber may fall behind by before it is disconnected.
+const EVENT_BUFFER: usize = 1024;
+/// How often the event thread checks for new events, and whether its node stopped.
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Name of the file in the storage dir of a node that is locked while the node runs.
+const STORE_LOCK_FILE: &str = "node.lock";
+
+/// Where a node is in its lifecycle. A node goes through one transition at a time, starting
+/// or stopping it meanwhile fails with `NodeBusy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NodeState {
+    #[default]
+    Stopped,
+    Starting,
+    Running,
+    Stopping,
 }
 
-pub fn get_our_address(node_name: String) -> String {
-    let config: WalletConfig = match WalletConfig::new(&node_name) {
-        Ok(c) => c,
-        Err(_) => return "".to_string(),
-    };
-    return config.get_listening_address();
+/// What the process keeps of a node it started, see `start_node`.
+#[derive(Default)]
+struct Lifecycle {
+    wallet_name: String,
+    state: NodeState,
+    /// Held from the start of the node until it is stopped, see `lock_store`.
+    store_lock: Option<File>,
+    /// Hands out the events of the node, ends once the node is stopped.
+    event_thread: Option<JoinHandle<()>>,
+    /// Open channels and on-chain sats of the node when it was last stopped.
+    funds_at_stop: Option<(usize, u64)>,
 }
 
-pub fn get_esplora_address(node_name: String) -> String {
-    let config: WalletConfig = match WalletConfig::new(&node_name) {
-        Ok(c) => c,
-        Err(_) => return "".to_string(),
-    };
-    return config.get_esplora_address();
+lazy_static! {
+    /// The running nodes, keyed by their storage dir.
+    pub(crate) static ref NODES: RwLock<HashMap<String, Arc<Node<SqliteStore>>>> = RwLock::new(HashMap::new());
+    /// Keyed like `NODES`, kept across restarts so subscribers outlive a node restart.
+    static ref EVENTS: RwLock<HashMap<String, broadcast::Sender<NodeEvent>>> =
+        RwLock::new(HashMap::new());
+    /// Keyed like `NODES`, the nodes started by this process, running or not.
+    static ref LIFECYCLES: Mutex<HashMap<String, Lifecycle>> = Mutex::new(HashMap::new());
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
-pub struct NodeConf {
-    pub network: ldk_node::bitcoin::Network,
-    pub storage_dir: String,
-    pub listening_address: String,
-    pub seed: Vec<u8>,
-    pub esplora_address: String,
+/// Lifecycles are updated field by field, so one left behind by a panicking thread is
+/// still consistent. It is recovered rather than leaving every node starting or stopping
+/// for good.
+fn lifecycles() -> MutexGuard<'static, HashMap<String, Lifecycle>> {
+    LIFECYCLES.lock().unwrap_or_else(PoisonError::into_inner)
 }
 
-lazy_static! {
-    static ref NODES: RwLock<HashMap<String, Arc<Node<SqliteStore>>>> = RwLock::new(HashMap::new());
+/// The channel the events of the node at `storage_dir` are fanned out on to its
+/// subscribers, created on first use.
+pub fn event_sender(storage_dir: &str) -> Result<broadcast::Sender<NodeEvent>, LightningError> {
+    let mut events = EVENTS
+        .write()
+        .map_err(|e| LightningError::NodesUnavailable(e.to_string()))?;
+    Ok(events
+        .entry(storage_dir.to_string())
+        .or_insert_with(|| broadcast::channel(EVENT_BUFFER).0)
+        .clone())
+}
+
+pub fn node_state(storage_dir: &str) -> Result<NodeState, LightningError> {
+    Ok(lifecycles()
+        .get(storage_dir)
+        .map(|lifecycle| lifecycle.state)
+        .unwrap_or_default())
+}
+
+/// The open channels and on-chain sats of the node when this process last stopped it.
+pub(crate) fn funds_at_stop(storage_dir: &str) -> Result<Option<(usize, u64)>, LightningError> {
+    Ok(lifecycles()
+        .get(storage_dir)
+        .and_then(|lifecycle| lifecycle.funds_at_stop))
+}
+
+/// Drops what is kept of a stopped node, once its wallet is deleted.
+pub(crate) fn forget_node(storage_dir: &str) -> Result<(), LightningError> {
+    lifecycles().remove(storage_dir);
+    Ok(())
+}
+
+/// Locks the store of the node, so that another server on the same data dir can't run the
+/// node too. ldk-node doesn't guard its SQLite store, and the lock is released with the
+/// file even if the process dies.
+fn lock_store(storage_dir: &str) -> Result<File, LightningError> {
+    let path = format!("{}/{}", storage_dir, STORE_LOCK_FILE);
+    let file = std::fs::create_dir_all(storage_dir)
+        .and_then(|()| {
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .mode(0o600)
+                .open(&path)
+        })
+        .map_err(|e| LightningError::DataDirUnavailable(format!("{}: {}", path, e)))?;
+    match file.try_lock() {
+        Ok(()) => Ok(file),
+        Err(TryLockError::WouldBlock) => Err(LightningError::StoreLocked(storage_dir.to_string())),
+        Err(TryLockError::Error(e)) => Err(LightningError::DataDirUnavailable(format!(
+            "{}: {}",
+            path, e
+        ))),
+    }
+}
+
+/// Starts the node unless it is running already. The node is registered in `NODES` once
+/// it runs, a node failing to start is left stopped.
+pub fn start_node(config: Arc<NodeConf>, defaults: &NodeDefaults) -> Result<(), LightningError> {
+    let storage_dir = config.storage_dir.clone();
+    {
+        let mut lifecycles = lifecycles();
+        let lifecycle = lifecycles.entry(storage_dir.clone()).or_default();
+        match lifecycle.state {
+            NodeState::Running => return Ok(()),
+            NodeState::Starting | NodeState::Stopping => {
+                return Err(LightningError::NodeBusy(config.wallet_name.clone()))
+            }
+            NodeState::Stopped => {
+                lifecycle.wallet_name = config.wallet_name.clone();
+                lifecycle.state = NodeState::Starting;
+            }
+        }
+    }
+    let started = lock_store(&storage_dir)
+        .and_then(|store_lock| Ok((store_lock, init_lazy(&config, defaults)?)));
+    let mut lifecycles = lifecycles();
+    let lifecycle = lifecycles.entry(storage_dir).or_default();
+    match started {
+        Ok((store_lock, event_thread)) => {
+            lifecycle.state = NodeState::Running;
+            lifecycle.store_lock = Some(store_lock);
+            lifecycle.event_thread = Some(event_thread);
+            Ok(())
+        }
+        Err(e) => {
+            lifecycle.state = NodeState::Stopped;
+            Err(e)
+        }
+    }
 }
 
-pub fn init_lazy(config: Arc<NodeConf>) -> (bool, String) {
+fn init_lazy(config: &NodeConf, defaults: &NodeDefaults) -> Result<JoinHandle<()>, LightningError> {
     let storage_dir = config.storage_dir.clone();
     let mut builder = Builder::new();
-    builder.set_network(Network::Testnet);
-    builder.set_log_level(LogLevel::Info);
+    let network = Network::try_from(config.network).map_err(|_| NodeError::InvalidNetwork)?;
+    builder.set_network(network);
+    builder.set_log_level(defaults.log_level);
     builder.set_storage_dir_path(storage_dir.clone());
     builder.set_log_dir_path(format!("{}/logs", &config.storage_dir));
-    let socket_address = match SocketAddress::from_str(&config.listening_address) {
-        Ok(s) => s,
-        Err(e) => {
-            return (false, e.to_string());
+    let socket_address = parse_socket_address(&config.listening_address)?;
+    let builder = builder.set_listening_addresses(vec![socket_address])?;
+    builder.set_esplora_server(config.esplora_address.clone());
+    if let Some(rgs_url) = defaults.rgs_url(config.network) {
+        builder.set_gossip_source_rgs(rgs_url.to_string());
+    }
+    // ldk-node takes its own copy of the seed, which is out of our hands
+    let builder = builder.set_entropy_seed_bytes(config.seed.as_bytes().to_vec())?;
+    let node = builder.build()?;
+    let node = Arc::new(node);
+    let mut journal = EventJournal::open(&storage_dir, config.event_retention_days)
+        .map_err(|e| LightningError::JournalUnavailable(e.to_string()))?;
+    let events = event_sender(&storage_dir)?;
+    node.start()?;
+    match NODES.write() {
+        Ok(mut nodes) => {
+            nodes.insert(storage_dir.clone(), node.clone());
         }
-    };
-    let builder = match builder.set_listening_addresses(vec![socket_address]) {
-        Ok(b) => b,
         Err(e) => {
-            return (false, e.to_string());
+            let _ = node.stop();
+            return Err(LightningError::NodesUnavailable(e.to_string()));
         }
-    };
-    builder.set_esplora_server(config.esplora_address.clone());
-    builder.set_gossip_source_rgs(
-        "https://rapidsync.lightningdevkit.org/testnet/snapshot".to_string(),
+    }
+    let webhooks = webhook::spawn(
+        storage_dir.clone(),
+        config.wallet_name.clone(),
+        config.webhooks.clone(),
     );
-    let builder = match builder.set_entropy_seed_bytes(config.seed.clone()) {
-        Ok(b) => b,
-        Err(e) => {
-            return (false, e.to_string());
+    // Ends once the node is stopped and its pending events are handed out, dead-lettering
+    // the webhook deliveries still queued.
+    let event_thread = thread::spawn(move || {
+        loop {
+            let event = match node.next_event() {
+                Some(event) => event,
+                None if node.is_running() => {
+                    thread::sleep(EVENT_POLL_INTERVAL);
+                    continue;
+                }
+                None => break,
+            };
+            let event = match journal.append(event.into()) {
+                Ok(event) => event,
+                // Left unhandled, the node hands the same event out again, after a restart
+                // if it was stopped.
+                Err(e) if !node.is_running() => {
+                    eprintln!("Unable to journal event: {}", e);
+                    break;
+                }
+                Err(e) => {
+                    eprintln!("Unable to journal event: {}", e);
+                    thread::sleep(Duration::from_secs(1));
+                    continue;
+                }
+            };
+            if let Some(webhooks) = &webhooks {
+                webhooks.send(&event);
+            }
+            // Sending only fails when nobody is subscribed.
+            let _ = events.send(event);
+            node.event_handled();
+        }
+        if let Some(webhooks) = webhooks {
+            webhooks.stop();
+        }
+    });
+    Ok(event_thread)
+}
+
+/// Stops the node unless it is stopped already, and waits for its pending events to be
+/// journaled. Webhook deliveries still pending are dead-lettered rather than waited for.
+pub fn stop_node(storage_dir: &str) -> Result<(), LightningError> {
+    {
+        let mut lifecycles = lifecycles();
+        match lifecycles.get_mut(storage_dir) {
+            Some(lifecycle) if lifecycle.state == NodeState::Running => {
+                lifecycle.state = NodeState::Stopping;
+            }
+            Some(lifecycle) if lifecycle.state != NodeState::Stopped => {
+                return Err(LightningError::NodeBusy(lifecycle.wallet_name.clone()))
+            }
+            _ => return Ok(()),
         }
-    };
-    let node = match builder.build() {
-        Ok(n) => n,
-        Err(e) => {
-            return (false, e.to_string());
+    }
+    let node = match NODES.write() {
+        Ok(mut nodes) => nodes.remove(storage_dir),
+        Err(e) => {
+            // the node was left running
+            if let Some(lifecycle) = lifecycles().get_mut(storage_dir) {
+                lifecycle.state = NodeState::Running;
+            }
+            return Err(LightningError::NodesUnavailable(e.to_string()));
+        }
+    };
+    // from here on the node ends up stopped
+    let mut funds_at_stop = None;
+    if let Some(node) = node {
+        let channels = node.list_channels().len();
+        funds_at_stop = node
+            .total_onchain_balance_sats()
+            .ok()
+            .map(|balance| (channels, balance));
+        match node.stop() {
+            Ok(()) | Err(NodeError::NotRunning) => {}
+            Err(e) => eprintln!("Unable to stop node {}: {}", storage_dir, e),
         }
-    };
-    let node = Arc::new(node);
-    let mut nodes = match NODES.write() {
-        Ok(n) => n,
-        Err(e) => {
-            return (false, e.to_string());
+    }
+    let event_thread = lifecycles()
+        .get_mut(storage_dir)
+        .and_then(|lifecycle| lifecycle.event_thread.take());
+    if let Some(event_thread) = event_thread {
+        if event_thread.join().is_err() {
+            eprintln!("The event thread of {} panicked", storage_dir);
         }
-    };
-    nodes.insert(storage_dir.clone(), node.clone());
-    match node.clone().start() {
-        Ok(_) => {
-            thread::spawn(move || loop {
-                let event = node.clone().wait_next_event();
-                println!("EVENT: {:?}", event);
-                node.event_handled();
-            });
-            (true, "".to_string())
+    }
+    if let Some(lifecycle) = lifecycles().get_mut(storage_dir) {
+        lifecycle.state = NodeState::Stopped;
+        lifecycle.store_lock = None;
+        lifecycle.funds_at_stop = funds_at_stop;
+    }
+    Ok(())
+}
+
+/// Stops every node started by this process, see `stop_node`.
+pub(crate) fn stop_all_nodes() -> Result<(), LightningError> {
+    let storage_dirs: Vec<String> = lifecycles().keys().cloned().collect();
+    for storage_dir in storage_dirs {
+        if let Err(e) = stop_node(&storage_dir) {
+            eprintln!("Unable to stop node {}: {}", storage_dir, e);
         }
-        Err(e) => {
-            return (false, e.to_string());
+    }
+    Ok(())
+}
+
+/// Ends every event subscription, for when no node will be started again.
+pub(crate) fn close_event_streams() -> Result<(), LightningError> {
+    EVENTS
+        .write()
+        .map_err(|e| LightningError::NodesUnavailable(e.to_string()))?
+        .clear();
+    Ok(())
+}
+
+impl From<Event> for NodeEvent {
+    fn from(event: Event) -> Self {
+        use crate::walletrpc::node_event;
+        let event = match event {
+            Event::PaymentReceived {
+                payment_hash,
+                amount_msat,
+            } => node_event::Event::PaymentReceived(walletrpc::PaymentReceivedEvent {
+                payment_hash: payment_hash.to_string(),
+                amount_msat,
+            }),
+            Event::PaymentSuccessful { payment_hash } => {
+                node_event::Event::PaymentSuccessful(walletrpc::PaymentSuccessfulEvent {
+                    payment_hash: payment_hash.to_string(),
+                })
+            }
+            Event::PaymentFailed { payment_hash } => {
+                node_event::Event::PaymentFailed(walletrpc::PaymentFailedEvent {
+                    payment_hash: payment_hash.to_string(),
+                })
+            }
+            Event::ChannelPending {
+                channel_id,
+                user_channel_id,
+                former_temporary_channel_id,
+                counterparty_node_id,
+                funding_txo,
+            } => node_event::Event::ChannelPending(walletrpc::ChannelPendingEvent {
+                channel_id: channel_id.to_string(),
+                user_channel_id: user_channel_id_to_hex(user_channel_id.0),
+                former_temporary_channel_id: former_temporary_channel_id.to_string(),
+                counterparty_node_id: counterparty_node_id.to_string(),
+                funding_txo: funding_txo.to_string(),
+            }),
+            Event::ChannelReady {
+                channel_id,
+                user_channel_id,
+                counterparty_node_id,
+            } => node_event::Event::ChannelReady(walletrpc::ChannelReadyEvent {
+                channel_id: channel_id.to_string(),
+                user_channel_id: user_channel_id_to_hex(user_channel_id.0),
+                counterparty_node_id: counterparty_node_id
+                    .map(|n| n.to_string())
+                    .unwrap_or_default(),
+            }),
+            Event::ChannelClosed {
+                channel_id,
+                user_channel_id,
+                counterparty_node_id,
+            } => node_event::Event::ChannelClosed(walletrpc::ChannelClosedEvent {
+                channel_id: channel_id.to_string(),
+                user_channel_id: user_channel_id_to_hex(user_channel_id.0),
+                counterparty_node_id: counterparty_node_id
+                    .map(|n| n.to_string())
+                    .unwrap_or_default(),
+            }),
+        };
+        NodeEvent {
+            event: Some(event),
+            ..Default::default()
         }
     }
 }
@@ -1,4 +0,0 @@
-pub mod paths;
-pub mod wallet;
-
-fn main() {}
@@ -0,0 +1,606 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use ldk_node::bitcoin::address::NetworkUnchecked;
+use ldk_node::bitcoin::secp256k1::PublicKey;
+use ldk_node::bitcoin::{Address, Network, Txid};
+use ldk_node::lightning::ln::msgs::SocketAddress;
+use ldk_node::lightning::ln::{ChannelId, PaymentHash};
+use ldk_node::lightning_invoice::Bolt11Invoice;
+use ldk_node::{NodeError, PaymentDetails, PaymentDirection, PaymentStatus};
+use tokio::sync::broadcast;
+
+use crate::config::NodeDefaults;
+use crate::journal;
+use crate::lightning::{
+    self, get_node, user_paths, Balances, ChanDetails, LightningError, NodeConf, NodeState,
+    WrappedPaymentDetails, WrappedPeerDetails,
+};
+use crate::wallet::{SecretMnemonic, SecretSeed, Wallet, WalletConfig, WalletError};
+use crate::walletrpc::{node_event, NodeEvent};
+use crate::webhook;
+
+/// When the last event of each payment was journaled, by hex encoded payment hash.
+fn payment_times(node_name: &str) -> Result<HashMap<String, u64>, LightningError> {
+    let events = journal::read_events(&user_paths()?.ldk_data_dir(node_name))
+        .map_err(|e| LightningError::JournalUnavailable(e.to_string()))?;
+    Ok(events
+        .into_iter()
+        .filter_map(|event| {
+            let payment_hash = match event.event? {
+                node_event::Event::PaymentReceived(e) => e.payment_hash,
+                node_event::Event::PaymentSuccessful(e) => e.payment_hash,
+                node_event::Event::PaymentFailed(e) => e.payment_hash,
+                _ => return None,
+            };
+            Some((payment_hash, event.timestamp))
+        })
+        .collect())
+}
+
+fn with_timestamp(payment: PaymentDetails, times: &HashMap<String, u64>) -> WrappedPaymentDetails {
+    let mut payment = WrappedPaymentDetails::from(payment);
+    payment.timestamp = times.get(&lightning::bytes_to_hex(&payment.hash)).copied();
+    payment
+}
+
+/// Creates, starts and drives the wallets of the project base dir, in-process. The
+/// gRPC server is an adapter on top of it.
+///
+/// Running nodes are kept per process, keyed by their storage dir, and the base dir is
+/// the process-wide one of `paths::set_project_base_dir`. Starting and stopping a node
+/// blocks and must not be done from within an async runtime, ldk-node runs its own.
+///
+/// Seeds are encrypted at rest, a wallet is unlocked with its password once per manager
+/// before its node can be started. Creating or restoring a wallet unlocks it.
+pub struct NodeManager {
+    defaults: NodeDefaults,
+    /// Decrypted seeds of the unlocked wallets, by wallet name.
+    seeds: Mutex<HashMap<String, SecretSeed>>,
+}
+
+impl NodeManager {
+    pub fn new(defaults: NodeDefaults) -> Self {
+        NodeManager {
+            defaults,
+            seeds: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn node_defaults(&self) -> &NodeDefaults {
+        &self.defaults
+    }
+
+    /// The given esplora server, or the configured default of the network.
+    fn esplora_address_or_default(
+        &self,
+        esplora_address: Option<&str>,
+        network: Network,
+    ) -> String {
+        esplora_address
+            .or_else(|| self.defaults.esplora_address(network))
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    /// Creates a wallet with a new seed encrypted with `password`, returning its mnemonic.
+    /// The seed is derived with the BIP39 `passphrase`, empty for none.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_wallet(
+        &self,
+        network: Network,
+        allow_mainnet: bool,
+        wallet_name: &str,
+        listening_address: &str,
+        esplora_address: Option<&str>,
+        passphrase: &str,
+        password: &str,
+    ) -> Result<SecretMnemonic, LightningError> {
+        let esplora_address = self.esplora_address_or_default(esplora_address, network);
+        let mnemonic = Wallet::new(
+            network,
+            allow_mainnet,
+            wallet_name,
+            listening_address,
+            &esplora_address,
+            passphrase,
+            password,
+        )?;
+        self.unlock_wallet(wallet_name, password)?;
+        Ok(mnemonic)
+    }
+
+    /// Recreates a wallet from its mnemonic, without starting its node.
+    #[allow(clippy::too_many_arguments)]
+    pub fn restore_wallet(
+        &self,
+        network: Network,
+        allow_mainnet: bool,
+        wallet_name: &str,
+        listening_address: &str,
+        esplora_address: Option<&str>,
+        mnemonic: &str,
+        passphrase: &str,
+        password: &str,
+    ) -> Result<(), LightningError> {
+        let esplora_address = self.esplora_address_or_default(esplora_address, network);
+        Wallet::restore(
+            network,
+            allow_mainnet,
+            wallet_name,
+            listening_address,
+            &esplora_address,
+            mnemonic,
+            passphrase,
+            password,
+        )?;
+        self.unlock_wallet(wallet_name, password)
+    }
+
+    /// Decrypts the seed of the wallet into memory so that its node can be started.
+    pub fn unlock_wallet(&self, wallet_name: &str, password: &str) -> Result<(), LightningError> {
+        let seed = Wallet::unlock(wallet_name, password)?;
+        self.seeds
+            .lock()
+            .map_err(|e| LightningError::NodesUnavailable(e.to_string()))?
+            .insert(wallet_name.to_string(), seed);
+        Ok(())
+    }
+
+    /// The mnemonic of the wallet, for backups. The password is checked even when the
+    /// wallet is unlocked.
+    pub fn export_mnemonic(
+        &self,
+        wallet_name: &str,
+        password: &str,
+    ) -> Result<SecretMnemonic, LightningError> {
+        Ok(Wallet::export_mnemonic(wallet_name, password)?)
+    }
+
+    pub fn change_password(
+        &self,
+        wallet_name: &str,
+        old_password: &str,
+        new_password: &str,
+    ) -> Result<(), LightningError> {
+        Ok(Wallet::change_password(
+            wallet_name,
+            old_password,
+            new_password,
+        )?)
+    }
+
+    pub fn list_wallets(&self) -> Result<Vec<String>, LightningError> {
+        Ok(Wallet::list_wallets()?)
+    }
+
+    pub fn wallet_config(&self, wallet_name: &str) -> Result<WalletConfig, LightningError> {
+        Ok(WalletConfig::new(wallet_name)?)
+    }
+
+    /// Takes effect the next time the node starts.
+    pub fn update_wallet_config(
+        &self,
+        wallet_name: &str,
+        esplora_address: &str,
+        listening_address: &str,
+        event_retention_days: Option<u32>,
+    ) -> Result<(), LightningError> {
+        Ok(Wallet::update_config(
+            wallet_name.to_string(),
+            esplora_address.to_string(),
+            listening_address.to_string(),
+            event_retention_days,
+        )?)
+    }
+
+    /// Archives the wallet of a stopped node. Unless forced, wallets that had open
+    /// channels or on-chain funds when their node was stopped are kept, as are wallets
+    /// whose node was never stopped by this process since their balances can't be checked.
+    pub fn delete_wallet(&self, node_name: &str, force: bool) -> Result<String, LightningError> {
+        let ldk_data_dir = user_paths()?.ldk_data_dir(node_name);
+        match lightning::node_state(&ldk_data_dir)? {
+            NodeState::Stopped => {}
+            NodeState::Running => return Err(LightningError::NodeRunning(node_name.to_string())),
+            NodeState::Starting | NodeState::Stopping => {
+                return Err(LightningError::NodeBusy(node_name.to_string()))
+            }
+        }
+        if !force {
+            match lightning::funds_at_stop(&ldk_data_dir)? {
+                Some((channels, balance)) => {
+                    if channels > 0 || balance > 0 {
+                        return Err(LightningError::WalletNotEmpty(format!(
+                            "{} channels, {} on-chain sats",
+                            channels, balance
+                        )));
+                    }
+                }
+                None => {
+                    let has_node_data = std::fs::read_dir(&ldk_data_dir)
+                        .map(|mut entries| entries.next().is_some())
+                        .unwrap_or(false);
+                    if has_node_data {
+                        return Err(LightningError::WalletNotEmpty(
+                            "start the node so that its balances can be checked".to_string(),
+                        ));
+                    }
+                }
+            }
+        }
+        let archive_dir = Wallet::archive(node_name)?;
+        self.seeds
+            .lock()
+            .map_err(|e| LightningError::NodesUnavailable(e.to_string()))?
+            .remove(node_name);
+        lightning::forget_node(&ldk_data_dir)?;
+        Ok(archive_dir)
+    }
+
+    /// Adds a webhook with a new random secret, which is returned. Running nodes pick it
+    /// up on their next start.
+    pub fn add_webhook(&self, wallet_name: &str, url: &str) -> Result<String, LightningError> {
+        let mut config = WalletConfig::new(wallet_name)?;
+        Ok(config.add_webhook(url.to_string())?)
+    }
+
+    pub fn remove_webhook(&self, wallet_name: &str, url: &str) -> Result<(), LightningError> {
+        let mut config = WalletConfig::new(wallet_name)?;
+        Ok(config.remove_webhook(url)?)
+    }
+
+    /// Sends a signed ping to one of the webhooks of the wallet, retrying like events are.
+    pub fn test_webhook(&self, wallet_name: &str, url: &str) -> Result<(), LightningError> {
+        let config = WalletConfig::new(wallet_name)?;
+        let webhook = config
+            .get_webhooks()
+            .into_iter()
+            .find(|w| w.url == url)
+            .ok_or(WalletError::WebhookNotFound(url.to_string()))?;
+        let ldk_data_dir = user_paths()?.ldk_data_dir(wallet_name);
+        webhook::deliver(&ldk_data_dir, wallet_name, &webhook, &webhook::ping())
+            .map_err(LightningError::WebhookDeliveryFailed)
+    }
+
+    /// Starts the node unless it is running already. Fails with `NodeBusy` while it is
+    /// being started or stopped, and with `StoreLocked` while another process runs it.
+    pub fn start_node(&self, node_name: &str) -> Result<(), LightningError> {
+        let user_paths = user_paths()?;
+        let config = WalletConfig::new(node_name)?;
+        let seed = self
+            .seeds
+            .lock()
+            .map_err(|e| LightningError::NodesUnavailable(e.to_string()))?
+            .get(node_name)
+            .cloned()
+            .ok_or_else(|| LightningError::WalletLocked(node_name.to_string()))?;
+        lightning::start_node(
+            Arc::new(NodeConf {
+                network: config.get_network(),
+                seed,
+                storage_dir: user_paths.ldk_data_dir(node_name),
+                listening_address: config.get_listening_address(),
+                esplora_address: config.get_esplora_address(),
+                event_retention_days: config.get_event_retention_days(),
+                wallet_name: node_name.to_string(),
+                webhooks: config.get_webhooks(),
+            }),
+            &self.defaults,
+        )
+    }
+
+    /// Stops the node unless it is stopped already, and waits for its pending events to be
+    /// journaled. Webhook deliveries still pending are dead-lettered rather than waited for.
+    pub fn stop_node(&self, node_name: &str) -> Result<(), LightningError> {
+        WalletConfig::new(node_name)?;
+        lightning::stop_node(&user_paths()?.ldk_data_dir(node_name))
+    }
+
+    /// Stops the node if it is running and starts it again, picking up changes to its
+    /// wallet config.
+    pub fn restart_node(&self, node_name: &str) -> Result<(), LightningError> {
+        self.stop_node(node_name)?;
+        self.start_node(node_name)
+    }
+
+    /// Stops every node like `stop_node` and ends the event subscriptions, for when the
+    /// process exits. Nodes failing to stop are logged and skipped.
+    pub fn shutdown(&self) -> Result<(), LightningError> {
+        lightning::stop_all_nodes()?;
+        lightning::close_event_streams()
+    }
+
+    /// Fails with `NodeNotFound` unless the node was started, for callers that report a
+    /// missing node before invalid arguments.
+    pub fn require_node(&self, node_name: &str) -> Result<(), LightningError> {
+        get_node(node_name)?;
+        Ok(())
+    }
+
+    pub fn is_node_running(&self, node_name: &str) -> Result<bool, LightningError> {
+        Ok(self.node_state(node_name)? == NodeState::Running)
+    }
+
+    pub fn node_state(&self, node_name: &str) -> Result<NodeState, LightningError> {
+        lightning::node_state(&user_paths()?.ldk_data_dir(node_name))
+    }
+
+    pub fn node_id(&self, node_name: &str) -> Result<PublicKey, LightningError> {
+        Ok(get_node(node_name)?.node_id())
+    }
+
+    /// The address the node listens on for peers.
+    pub fn listening_address(&self, node_name: &str) -> Result<String, LightningError> {
+        Ok(WalletConfig::new(node_name)?.get_listening_address())
+    }
+
+    pub fn esplora_address(&self, node_name: &str) -> Result<String, LightningError> {
+        Ok(WalletConfig::new(node_name)?.get_esplora_address())
+    }
+
+    pub fn new_onchain_address(&self, node_name: &str) -> Result<Address, LightningError> {
+        Ok(get_node(node_name)?.new_onchain_address()?)
+    }
+
+    pub fn total_onchain_balance(&self, node_name: &str) -> Result<u64, LightningError> {
+        Ok(get_node(node_name)?.total_onchain_balance_sats()?)
+    }
+
+    /// On-chain and lightning balances of a node, gathered in one go.
+    pub fn balances(&self, node_name: &str) -> Result<Balances, LightningError> {
+        let node = get_node(node_name)?;
+        let channels: Vec<ChanDetails> = node
+            .list_channels()
+            .into_iter()
+            .map(ChanDetails::from)
+            .collect();
+        Ok(Balances {
+            total_onchain_balance_sats: node.total_onchain_balance_sats()?,
+            spendable_onchain_balance_sats: node.spendable_onchain_balance_sats()?,
+            total_lightning_balance_msat: channels.iter().map(|c| c.balance_msat).sum(),
+            outbound_capacity_msat: channels.iter().map(|c| c.outbound_capacity_msat).sum(),
+            inbound_capacity_msat: channels.iter().map(|c| c.inbound_capacity_msat).sum(),
+            channels,
+        })
+    }
+
+    /// Checks that the address belongs to the network of the wallet.
+    fn require_network(
+        &self,
+        node_name: &str,
+        address: Address<NetworkUnchecked>,
+    ) -> Result<Address, LightningError> {
+        let network = WalletConfig::new(node_name)?.get_network();
+        if !address.is_valid_for_network(network) {
+            return Err(LightningError::AddressNetworkMismatch(format!(
+                "{} is not a {} address",
+                address.assume_checked(),
+                network
+            )));
+        }
+        Ok(address.assume_checked())
+    }
+
+    pub fn send_to_address(
+        &self,
+        node_name: &str,
+        address: Address<NetworkUnchecked>,
+        amount_sats: u64,
+        fee_rate_sat_per_vb: Option<u64>,
+    ) -> Result<Txid, LightningError> {
+        if fee_rate_sat_per_vb.is_some() {
+            return Err(LightningError::FeeRateUnsupported);
+        }
+        if amount_sats == 0 {
+            return Err(NodeError::InvalidAmount.into());
+        }
+        let address = self.require_network(node_name, address)?;
+        let node = get_node(node_name)?;
+        Ok(node.send_to_onchain_address(&address, amount_sats)?)
+    }
+
+    /// Sends every spendable on-chain sat to the given address.
+    pub fn sweep_all(
+        &self,
+        node_name: &str,
+        address: Address<NetworkUnchecked>,
+    ) -> Result<Txid, LightningError> {
+        let address = self.require_network(node_name, address)?;
+        let node = get_node(node_name)?;
+        Ok(node.send_all_to_onchain_address(&address)?)
+    }
+
+    /// Connects to the peer if needed and opens a channel to it. A push of 1 msat or less
+    /// pushes nothing.
+    pub fn open_channel(
+        &self,
+        node_name: &str,
+        node_id: PublicKey,
+        net_address: SocketAddress,
+        channel_amount_sats: u64,
+        push_to_counterparty_msat: u64,
+        announce_channel: bool,
+    ) -> Result<(), LightningError> {
+        let node = get_node(node_name)?;
+        let push_to_counterparty_msat: Option<u64> = if push_to_counterparty_msat > 1 {
+            Some(push_to_counterparty_msat)
+        } else {
+            None
+        };
+        let channel_config = None;
+        node.connect_open_channel(
+            node_id,
+            net_address,
+            channel_amount_sats,
+            push_to_counterparty_msat,
+            channel_config,
+            announce_channel,
+        )?;
+        Ok(())
+    }
+
+    pub fn close_channel(
+        &self,
+        node_name: &str,
+        channel_id: &ChannelId,
+        counterparty_node_id: PublicKey,
+    ) -> Result<(), LightningError> {
+        let node = get_node(node_name)?;
+        node.close_channel(channel_id, counterparty_node_id)?;
+        Ok(())
+    }
+
+    pub fn list_channels(&self, node_name: &str) -> Result<Vec<ChanDetails>, LightningError> {
+        let node = get_node(node_name)?;
+        Ok(node
+            .list_channels()
+            .into_iter()
+            .map(ChanDetails::from)
+            .collect())
+    }
+
+    /// Connects to a peer, reconnecting to it after restarts.
+    pub fn connect_to_peer(
+        &self,
+        node_name: &str,
+        node_id: PublicKey,
+        net_address: SocketAddress,
+    ) -> Result<(), LightningError> {
+        let persist = true;
+        let node = get_node(node_name)?;
+        node.connect(node_id, net_address, persist)?;
+        Ok(())
+    }
+
+    pub fn disconnect_peer(
+        &self,
+        node_name: &str,
+        node_id: PublicKey,
+    ) -> Result<(), LightningError> {
+        let node = get_node(node_name)?;
+        node.disconnect(node_id)?;
+        Ok(())
+    }
+
+    pub fn list_peers(&self, node_name: &str) -> Result<Vec<WrappedPeerDetails>, LightningError> {
+        let node = get_node(node_name)?;
+        Ok(node
+            .list_peers()
+            .into_iter()
+            .map(WrappedPeerDetails::from)
+            .collect())
+    }
+
+    pub fn create_invoice(
+        &self,
+        node_name: &str,
+        amount_msat: u64,
+        description: &str,
+        expiry_secs: u32,
+    ) -> Result<Bolt11Invoice, LightningError> {
+        let node = get_node(node_name)?;
+        Ok(node.receive_payment(amount_msat, description, expiry_secs)?)
+    }
+
+    /// Returns the payment hash once the payment is sent, it then succeeds or fails
+    /// asynchronously.
+    pub fn pay_invoice(
+        &self,
+        node_name: &str,
+        invoice: &Bolt11Invoice,
+    ) -> Result<PaymentHash, LightningError> {
+        let node = get_node(node_name)?;
+        Ok(node.send_payment(invoice)?)
+    }
+
+    /// Lists the payments matching the given filters, ordered by hash so that pages stay
+    /// stable between calls. Returns the requested page along with the number of matches.
+    /// A `limit` of 0 returns every payment after `offset`.
+    /// Lists the payments matching the filters, ordered by time like `ListPayments` in the
+    /// proto file, with the total count of matching payments ignoring pagination.
+    #[allow(clippy::too_many_arguments)]
+    pub fn list_payments(
+        &self,
+        node_name: &str,
+        direction: Option<PaymentDirection>,
+        status: Option<PaymentStatus>,
+        since: Option<u64>,
+        until: Option<u64>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<WrappedPaymentDetails>, usize), LightningError> {
+        let node = get_node(node_name)?;
+        let times = payment_times(node_name)?;
+        let mut payments: Vec<WrappedPaymentDetails> = node
+            .list_payments_with_filter(|p| {
+                direction.is_none_or(|d| p.direction == d) && status.is_none_or(|s| p.status == s)
+            })
+            .into_iter()
+            .map(|p| with_timestamp(p, &times))
+            .filter(|p| match p.timestamp {
+                Some(timestamp) => {
+                    since.is_none_or(|since| timestamp >= since)
+                        && until.is_none_or(|until| timestamp < until)
+                }
+                None => since.is_none() && until.is_none(),
+            })
+            .collect();
+        // settled before the journal was pruned, journaled, then pending
+        payments.sort_by_key(|p| match (p.timestamp, p.status) {
+            (Some(timestamp), _) => (1, timestamp, p.hash),
+            (None, PaymentStatus::Pending) => (2, 0, p.hash),
+            (None, _) => (0, 0, p.hash),
+        });
+        let total = payments.len();
+        let limit = if limit == 0 { usize::MAX } else { limit };
+        let page = payments.into_iter().skip(offset).take(limit).collect();
+        Ok((page, total))
+    }
+
+    pub fn payment(
+        &self,
+        node_name: &str,
+        payment_hash: &PaymentHash,
+    ) -> Result<WrappedPaymentDetails, LightningError> {
+        let node = get_node(node_name)?;
+        match node.payment(payment_hash) {
+            Some(payment) => Ok(with_timestamp(payment, &payment_times(node_name)?)),
+            None => Err(LightningError::PaymentNotFound(lightning::bytes_to_hex(
+                &payment_hash.0,
+            ))),
+        }
+    }
+
+    /// Subscribes to the events of a started node, from now on.
+    pub fn subscribe_events(
+        &self,
+        node_name: &str,
+    ) -> Result<broadcast::Receiver<NodeEvent>, LightningError> {
+        get_node(node_name)?;
+        Ok(lightning::event_sender(&user_paths()?.ldk_data_dir(node_name))?.subscribe())
+    }
+
+    /// Returns up to `limit` journaled events that come after `after_seq`, all of them for
+    /// a `limit` of 0. The journal is read from disk so the node doesn't have to be running.
+    pub fn replay_events(
+        &self,
+        node_name: &str,
+        after_seq: u64,
+        limit: usize,
+    ) -> Result<Vec<NodeEvent>, LightningError> {
+        WalletConfig::new(node_name)?;
+        let events = journal::read_events(&user_paths()?.ldk_data_dir(node_name))
+            .map_err(|e| LightningError::JournalUnavailable(e.to_string()))?;
+        if let Some(oldest) = events.first() {
+            if after_seq > 0 && after_seq.saturating_add(1) < oldest.seq {
+                return Err(LightningError::EventsPruned(oldest.seq));
+            }
+        }
+        let limit = if limit == 0 { usize::MAX } else { limit };
+        Ok(events
+            .into_iter()
+            .filter(|e| e.seq > after_seq)
+            .take(limit)
+            .collect())
+    }
+}
@@ -1,33 +1,76 @@
 use directories::UserDirs;
+use std::io;
+use std::sync::OnceLock;
 
 const PROJECT_FOLDER_NAME: &str = ".bits-wallet";
 
-pub struct UserPaths;
+/// Replaces `home_dir/.bits-wallet` when set, once at startup.
+static PROJECT_BASE_DIR: OnceLock<String> = OnceLock::new();
+
+/// Keeps every file of the project under `dir`. Returns false if it was already set.
+pub fn set_project_base_dir(dir: String) -> bool {
+    PROJECT_BASE_DIR.set(dir).is_ok()
+}
+
+pub struct UserPaths {
+    home_dir: String,
+}
 
 impl UserPaths {
-    pub fn new() -> Self {
-        UserPaths
+    pub fn new() -> io::Result<Self> {
+        let user_dirs = UserDirs::new().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "Could not find home directory")
+        })?;
+        let home_dir = user_dirs.home_dir().to_str().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Could not convert home directory to string",
+            )
+        })?;
+        Ok(UserPaths {
+            home_dir: home_dir.to_string(),
+        })
     }
 
     pub fn home_dir(&self) -> String {
-        let home_dir = match UserDirs::new() {
-            Some(user_dirs) => match user_dirs.home_dir().to_str() {
-                Some(home_dir) => home_dir.to_string(),
-                None => panic!("Could not convert home directory to string"),
-            },
-            None => panic!("Could not find home directory"),
-        };
-        home_dir
+        self.home_dir.clone()
     }
 
     pub fn project_base_dir(&self) -> String {
-        format!("{}/{}", self.home_dir(), PROJECT_FOLDER_NAME)
+        match PROJECT_BASE_DIR.get() {
+            Some(dir) => dir.clone(),
+            None => format!("{}/{}", self.home_dir(), PROJECT_FOLDER_NAME),
+        }
+    }
+
+    pub fn tls_cert_file(&self) -> String {
+        format!("{}/tls.cert", self.project_base_dir())
+    }
+
+    pub fn tls_key_file(&self) -> String {
+        format!("{}/tls.key", self.project_base_dir())
+    }
+
+    pub fn socket_file(&self) -> String {
+        format!("{}/walletrpc.sock", self.project_base_dir())
+    }
+
+    pub fn tokens_file(&self) -> String {
+        format!("{}/tokens.json", self.project_base_dir())
+    }
+
+    pub fn admin_token_file(&self) -> String {
+        format!("{}/admin.token", self.project_base_dir())
     }
 
     pub fn wallets_dir(&self) -> String {
         format!("{}/wallets", self.project_base_dir())
     }
 
+    pub fn archive_dir(&self) -> String {
+        format!("{}/archive", self.project_base_dir())
+    }
+
     pub fn wallet_dir(&self, wallet_name: &str) -> String {
         format!("{}/{}", self.wallets_dir(), wallet_name)
     }
@@ -36,6 +79,10 @@ impl UserPaths {
         format!("{}/seed", self.wallet_dir(wallet_name))
     }
 
+    pub fn mnemonic_file(&self, wallet_name: &str) -> String {
+        format!("{}/mnemonic", self.wallet_dir(wallet_name))
+    }
+
     pub fn config_file(&self, wallet_name: &str) -> String {
         format!("{}/config.json", self.wallet_dir(wallet_name))
     }
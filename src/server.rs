@@ -1,32 +1,62 @@
-extern crate lazy_static;
 use std::collections::HashMap;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
 
-use ldk_node::bitcoin::Network;
-use ldk_node::bitcoin::secp256k1::PublicKey;
-use ldk_node::lightning::ln::ChannelId;
-use lightning::ChanDetails;
+use ldk_node::{PaymentDirection, PaymentStatus};
+use tokio::net::UnixListener;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::{BroadcastStream, UnixListenerStream};
+use tokio_stream::{Stream, StreamExt};
 use tonic::{transport::Server, Request, Response, Status};
 use walletrpc::wallet_rpc_service_server::{WalletRpcService, WalletRpcServiceServer};
 use walletrpc::{
-    Channel, CloseChannelRequest, ConnectToPeerRequest, CreateInvoiceReply, CreateInvoiceRequest,
-    DisconnectPeerRequest, GeneralNodeNameRequest, GeneralSuccessReply, GetNodeIdReply,
-    GetOnChainAddressReply, GetOnChainBalanceReply, ListChannelsReply, ListPeersReply,
-    NewWalletReply, NewWalletRequest, OpenChannelRequest, PayInvoiceRequest, Peer, StartNodeReply,
-	GetEsploraAddressReply, GetNetAddressReply,
+    AddWebhookReply, BakeTokenReply, BakeTokenRequest, ChangePasswordRequest, Channel,
+    CloseChannelRequest, ConnectToPeerRequest, CreateInvoiceReply, CreateInvoiceRequest,
+    DeleteWalletReply, DeleteWalletRequest, DisconnectPeerRequest, ExportMnemonicReply,
+    ExportMnemonicRequest, GeneralNodeNameRequest, GeneralSuccessReply, GetBalancesReply,
+    GetEsploraAddressReply, GetNetAddressReply, GetNodeIdReply, GetOnChainAddressReply,
+    GetOnChainBalanceReply, GetPaymentRequest, GetWalletConfigReply, ListChannelsReply,
+    ListPaymentsReply, ListPaymentsRequest, ListPeersReply, ListTokensReply, ListTokensRequest,
+    ListWalletsReply, ListWalletsRequest, NewWalletReply, NewWalletRequest, NodeEvent,
+    OpenChannelRequest, PayInvoiceRequest, Payment, Peer, ReplayEventsReply, ReplayEventsRequest,
+    RestoreWalletReply, RestoreWalletRequest, RevokeTokenRequest, SendOnChainReply,
+    SendToAddressRequest, StartNodeReply, SweepAllRequest, UnlockWalletRequest,
+    UpdateWalletConfigRequest, WebhookRequest,
 };
+use walletrpc_tonic::auth::{self, Permission, TokenStore};
+use walletrpc_tonic::config::{ListenAddress, ServerConfig};
+use walletrpc_tonic::lightning::{self, LightningError};
+use walletrpc_tonic::wallet::WalletError;
+use walletrpc_tonic::{paths, tls, walletrpc, NodeManager};
 
-use crate::lightning::WrappedPeerDetails;
-pub mod lightning;
-pub mod paths;
-pub mod wallet;
+/// The gRPC adapter of the node manager, it authorizes every call and converts between
+/// the generated types and the ones of the manager.
+pub struct MyWallet {
+    tokens: Arc<TokenStore>,
+    manager: Arc<NodeManager>,
+}
 
-pub mod walletrpc {
-    // The string specified here must match the proto package name
-    tonic::include_proto!("walletrpc");
+/// The request's esplora address, empty for the configured default.
+fn esplora_address(esplora_address: &str) -> Option<&str> {
+    Some(esplora_address).filter(|address| !address.is_empty())
 }
 
-#[derive(Debug, Default)]
-pub struct MyWallet {}
+/// Starting and stopping a node creates and drops its own tokio runtime, which
+/// panics when done from an async context, so those calls run on a blocking thread.
+/// So do the calls deriving the seed key from a password, which takes a while.
+async fn blocking<T, F>(f: F) -> Result<T, Status>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))
+}
 
 #[tonic::async_trait]
 impl WalletRpcService for MyWallet {
@@ -34,18 +64,284 @@ impl WalletRpcService for MyWallet {
         &self,
         request: Request<NewWalletRequest>,
     ) -> Result<Response<NewWalletReply>, Status> {
+        // the request is not printed as it contains the password
+        println!("Got a request: NewWallet");
+        auth::authorize(
+            &request,
+            Permission::Admin,
+            Some(&request.get_ref().wallet_name),
+        )?;
+        let request = request.into_inner();
+        let network = walletrpc::Network::try_from(request.network)
+            .map_err(|_| WalletError::InvalidNetwork(request.network.to_string()))?;
+        let manager = self.manager.clone();
+        let mnemonic = blocking(move || {
+            manager.create_wallet(
+                network.into(),
+                request.allow_mainnet,
+                &request.wallet_name,
+                &request.listening_address,
+                esplora_address(&request.esplora_address),
+                &request.passphrase,
+                &request.password,
+            )
+        })
+        .await??;
+        let reply = NewWalletReply {
+            mnemonic: mnemonic.phrase().to_string(),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn restore_wallet(
+        &self,
+        request: Request<RestoreWalletRequest>,
+    ) -> Result<Response<RestoreWalletReply>, Status> {
+        // the request is not printed as it contains the mnemonic and password
+        println!("Got a request: RestoreWallet");
+        auth::authorize(
+            &request,
+            Permission::Admin,
+            Some(&request.get_ref().wallet_name),
+        )?;
+        let request = request.into_inner();
+        let network = walletrpc::Network::try_from(request.network)
+            .map_err(|_| WalletError::InvalidNetwork(request.network.to_string()))?;
+        let wallet_name = request.wallet_name.clone();
+        let manager = self.manager.clone();
+        blocking(move || {
+            manager.restore_wallet(
+                network.into(),
+                request.allow_mainnet,
+                &request.wallet_name,
+                &request.listening_address,
+                esplora_address(&request.esplora_address),
+                &request.mnemonic,
+                &request.passphrase,
+                &request.password,
+            )
+        })
+        .await??;
+        let manager = self.manager.clone();
+        let node_name = wallet_name.clone();
+        let node_id = blocking(move || {
+            manager.start_node(&node_name)?;
+            manager.node_id(&node_name)
+        })
+        .await?
+        .map_err(|e| {
+            // keeps the code and detail of the failed start
+            let status = Status::from(e);
+            Status::with_details(
+                status.code(),
+                format!(
+                    "Wallet {} was restored but its node failed to start: {}",
+                    wallet_name,
+                    status.message()
+                ),
+                status.details().to_vec().into(),
+            )
+        })?;
+        let reply = RestoreWalletReply {
+            node_id: node_id.to_string(),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn unlock_wallet(
+        &self,
+        request: Request<UnlockWalletRequest>,
+    ) -> Result<Response<GeneralSuccessReply>, Status> {
+        // the request is not printed as it contains the password
+        println!("Got a request: UnlockWallet");
+        auth::authorize(
+            &request,
+            Permission::Admin,
+            Some(&request.get_ref().wallet_name),
+        )?;
+        let request = request.into_inner();
+        let manager = self.manager.clone();
+        blocking(move || manager.unlock_wallet(&request.wallet_name, &request.password)).await??;
+        let reply = GeneralSuccessReply { success: true };
+        Ok(Response::new(reply))
+    }
+
+    async fn change_password(
+        &self,
+        request: Request<ChangePasswordRequest>,
+    ) -> Result<Response<GeneralSuccessReply>, Status> {
+        // the request is not printed as it contains the passwords
+        println!("Got a request: ChangePassword");
+        auth::authorize(
+            &request,
+            Permission::Admin,
+            Some(&request.get_ref().wallet_name),
+        )?;
+        let request = request.into_inner();
+        let manager = self.manager.clone();
+        blocking(move || {
+            manager.change_password(
+                &request.wallet_name,
+                &request.old_password,
+                &request.new_password,
+            )
+        })
+        .await??;
+        let reply = GeneralSuccessReply { success: true };
+        Ok(Response::new(reply))
+    }
+
+    async fn export_mnemonic(
+        &self,
+        request: Request<ExportMnemonicRequest>,
+    ) -> Result<Response<ExportMnemonicReply>, Status> {
+        // the request is not printed as it contains the password
+        println!("Got a request: ExportMnemonic");
+        auth::authorize(
+            &request,
+            Permission::Admin,
+            Some(&request.get_ref().wallet_name),
+        )?;
+        let request = request.into_inner();
+        let manager = self.manager.clone();
+        let mnemonic =
+            blocking(move || manager.export_mnemonic(&request.wallet_name, &request.password))
+                .await??;
+        let reply = ExportMnemonicReply {
+            mnemonic: mnemonic.phrase().to_string(),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn list_wallets(
+        &self,
+        request: Request<ListWalletsRequest>,
+    ) -> Result<Response<ListWalletsReply>, Status> {
+        println!("Got a request: {:?}", request);
+        // every token can read, a scoped one only sees its own wallets
+        let grant = auth::grant(&request)?;
+        let wallet_names = self
+            .manager
+            .list_wallets()?
+            .into_iter()
+            .filter(|name| grant.allows_wallet(name))
+            .collect();
+        let reply = ListWalletsReply { wallet_names };
+        Ok(Response::new(reply))
+    }
+
+    async fn get_wallet_config(
+        &self,
+        request: Request<GeneralNodeNameRequest>,
+    ) -> Result<Response<GetWalletConfigReply>, Status> {
+        println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::ReadOnly,
+            Some(&request.get_ref().node_name),
+        )?;
+        let request = request.into_inner();
+        let config = self.manager.wallet_config(&request.node_name)?;
+        let network = walletrpc::Network::try_from(config.get_network())?;
+        let reply = GetWalletConfigReply {
+            wallet_name: config.get_wallet_name(),
+            network: network as i32,
+            listening_address: config.get_listening_address(),
+            esplora_address: config.get_esplora_address(),
+            event_retention_days: config.get_event_retention_days(),
+            webhook_urls: config.get_webhooks().into_iter().map(|w| w.url).collect(),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn update_wallet_config(
+        &self,
+        request: Request<UpdateWalletConfigRequest>,
+    ) -> Result<Response<GeneralSuccessReply>, Status> {
         println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::Admin,
+            Some(&request.get_ref().wallet_name),
+        )?;
         let request = request.into_inner();
-        let mnemonic = wallet::Wallet::new(
-            Network::Testnet,
+        self.manager.update_wallet_config(
             &request.wallet_name,
-            &request.listening_address,
             &request.esplora_address,
-        )
-        .unwrap();
-        let reply = NewWalletReply {
-            mnemonic: mnemonic.to_string(),
-        };
+            &request.listening_address,
+            request.event_retention_days,
+        )?;
+        let reply = GeneralSuccessReply { success: true };
+        Ok(Response::new(reply))
+    }
+
+    async fn delete_wallet(
+        &self,
+        request: Request<DeleteWalletRequest>,
+    ) -> Result<Response<DeleteWalletReply>, Status> {
+        println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::Admin,
+            Some(&request.get_ref().wallet_name),
+        )?;
+        let request = request.into_inner();
+        let archive_dir = self
+            .manager
+            .delete_wallet(&request.wallet_name, request.force)?;
+        let reply = DeleteWalletReply { archive_dir };
+        Ok(Response::new(reply))
+    }
+
+    async fn add_webhook(
+        &self,
+        request: Request<WebhookRequest>,
+    ) -> Result<Response<AddWebhookReply>, Status> {
+        println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::Admin,
+            Some(&request.get_ref().wallet_name),
+        )?;
+        let request = request.into_inner();
+        let secret = self
+            .manager
+            .add_webhook(&request.wallet_name, &request.url)?;
+        let reply = AddWebhookReply { secret };
+        Ok(Response::new(reply))
+    }
+
+    async fn remove_webhook(
+        &self,
+        request: Request<WebhookRequest>,
+    ) -> Result<Response<GeneralSuccessReply>, Status> {
+        println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::Admin,
+            Some(&request.get_ref().wallet_name),
+        )?;
+        let request = request.into_inner();
+        self.manager
+            .remove_webhook(&request.wallet_name, &request.url)?;
+        let reply = GeneralSuccessReply { success: true };
+        Ok(Response::new(reply))
+    }
+
+    async fn test_webhook(
+        &self,
+        request: Request<WebhookRequest>,
+    ) -> Result<Response<GeneralSuccessReply>, Status> {
+        println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::Admin,
+            Some(&request.get_ref().wallet_name),
+        )?;
+        let request = request.into_inner();
+        let manager = self.manager.clone();
+        blocking(move || manager.test_webhook(&request.wallet_name, &request.url)).await??;
+        let reply = GeneralSuccessReply { success: true };
         Ok(Response::new(reply))
     }
 
@@ -54,12 +350,18 @@ impl WalletRpcService for MyWallet {
         request: Request<GeneralNodeNameRequest>,
     ) -> Result<Response<StartNodeReply>, Status> {
         println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::Admin,
+            Some(&request.get_ref().node_name),
+        )?;
         let request = request.into_inner();
         let node_name = request.node_name;
-        let response = lightning::start_node(node_name);
+        let manager = self.manager.clone();
+        blocking(move || manager.start_node(&node_name)).await??;
         let reply = StartNodeReply {
-            success: response.0,
-            msg: response.1,
+            success: true,
+            msg: "".to_string(),
         };
         Ok(Response::new(reply))
     }
@@ -69,9 +371,14 @@ impl WalletRpcService for MyWallet {
         request: Request<GeneralNodeNameRequest>,
     ) -> Result<Response<GeneralSuccessReply>, Status> {
         println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::ReadOnly,
+            Some(&request.get_ref().node_name),
+        )?;
         let request = request.into_inner();
         let node_name = request.node_name;
-        let response = lightning::is_node_running(node_name);
+        let response = self.manager.is_node_running(&node_name)?;
         let reply = GeneralSuccessReply { success: response };
         Ok(Response::new(reply))
     }
@@ -81,10 +388,17 @@ impl WalletRpcService for MyWallet {
         request: Request<GeneralNodeNameRequest>,
     ) -> Result<Response<GetNodeIdReply>, Status> {
         println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::ReadOnly,
+            Some(&request.get_ref().node_name),
+        )?;
         let request = request.into_inner();
         let node_name = request.node_name;
-        let response = lightning::get_node_id(node_name);
-        let reply = GetNodeIdReply { node_id: response };
+        let response = self.manager.node_id(&node_name)?;
+        let reply = GetNodeIdReply {
+            node_id: response.to_string(),
+        };
         Ok(Response::new(reply))
     }
 
@@ -93,10 +407,37 @@ impl WalletRpcService for MyWallet {
         request: Request<GeneralNodeNameRequest>,
     ) -> Result<Response<GeneralSuccessReply>, Status> {
         println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::Admin,
+            Some(&request.get_ref().node_name),
+        )?;
         let request = request.into_inner();
         let node_name = request.node_name;
-        let response = lightning::stop_node(node_name);
-        let reply = GeneralSuccessReply { success: response };
+        let manager = self.manager.clone();
+        blocking(move || manager.stop_node(&node_name)).await??;
+        let reply = GeneralSuccessReply { success: true };
+        Ok(Response::new(reply))
+    }
+
+    async fn restart_node(
+        &self,
+        request: Request<GeneralNodeNameRequest>,
+    ) -> Result<Response<StartNodeReply>, Status> {
+        println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::Admin,
+            Some(&request.get_ref().node_name),
+        )?;
+        let request = request.into_inner();
+        let node_name = request.node_name;
+        let manager = self.manager.clone();
+        blocking(move || manager.restart_node(&node_name)).await??;
+        let reply = StartNodeReply {
+            success: true,
+            msg: "".to_string(),
+        };
         Ok(Response::new(reply))
     }
 
@@ -105,12 +446,17 @@ impl WalletRpcService for MyWallet {
         request: Request<PayInvoiceRequest>,
     ) -> Result<Response<GeneralSuccessReply>, Status> {
         println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::Admin,
+            Some(&request.get_ref().our_node_name),
+        )?;
         let request = request.into_inner();
         let node_name = request.our_node_name;
-        let invoice = request.invoice;
-        let response = lightning::pay_invoice(node_name, invoice);
-        let success = if let Some(_r) = response { true } else { false };
-        let reply = GeneralSuccessReply { success };
+        self.manager.require_node(&node_name)?;
+        let invoice = lightning::parse_invoice(&request.invoice)?;
+        self.manager.pay_invoice(&node_name, &invoice)?;
+        let reply = GeneralSuccessReply { success: true };
         Ok(Response::new(reply))
     }
 
@@ -119,10 +465,17 @@ impl WalletRpcService for MyWallet {
         request: Request<GeneralNodeNameRequest>,
     ) -> Result<Response<GetOnChainAddressReply>, Status> {
         println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::Invoice,
+            Some(&request.get_ref().node_name),
+        )?;
         let request = request.into_inner();
         let node_name = request.node_name;
-        let response = lightning::new_onchain_address(node_name);
-        let reply = GetOnChainAddressReply { address: response };
+        let response = self.manager.new_onchain_address(&node_name)?;
+        let reply = GetOnChainAddressReply {
+            address: response.to_string(),
+        };
         Ok(Response::new(reply))
     }
 
@@ -131,33 +484,210 @@ impl WalletRpcService for MyWallet {
         request: Request<GeneralNodeNameRequest>,
     ) -> Result<Response<GetOnChainBalanceReply>, Status> {
         println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::ReadOnly,
+            Some(&request.get_ref().node_name),
+        )?;
         let request = request.into_inner();
         let node_name = request.node_name;
-        let response = lightning::total_onchain_balance(node_name);
+        let response = self.manager.total_onchain_balance(&node_name)?;
         let reply = GetOnChainBalanceReply {
             balance: response as i64,
         };
         Ok(Response::new(reply))
     }
 
+    async fn send_to_address(
+        &self,
+        request: Request<SendToAddressRequest>,
+    ) -> Result<Response<SendOnChainReply>, Status> {
+        println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::Admin,
+            Some(&request.get_ref().our_node_name),
+        )?;
+        let request = request.into_inner();
+        let address = lightning::parse_address(&request.address)?;
+        let txid = self.manager.send_to_address(
+            &request.our_node_name,
+            address,
+            request.amount_sats,
+            request.fee_rate_sat_per_vb,
+        )?;
+        let reply = SendOnChainReply {
+            txid: txid.to_string(),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn sweep_all(
+        &self,
+        request: Request<SweepAllRequest>,
+    ) -> Result<Response<SendOnChainReply>, Status> {
+        println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::Admin,
+            Some(&request.get_ref().our_node_name),
+        )?;
+        let request = request.into_inner();
+        let address = lightning::parse_address(&request.address)?;
+        let txid = self.manager.sweep_all(&request.our_node_name, address)?;
+        let reply = SendOnChainReply {
+            txid: txid.to_string(),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn get_balances(
+        &self,
+        request: Request<GeneralNodeNameRequest>,
+    ) -> Result<Response<GetBalancesReply>, Status> {
+        println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::ReadOnly,
+            Some(&request.get_ref().node_name),
+        )?;
+        let request = request.into_inner();
+        let node_name = request.node_name;
+        let response = self.manager.balances(&node_name)?;
+        let reply: GetBalancesReply = response.into();
+        Ok(Response::new(reply))
+    }
+
     async fn create_invoice(
         &self,
         request: Request<CreateInvoiceRequest>,
     ) -> Result<Response<CreateInvoiceReply>, Status> {
         println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::Invoice,
+            Some(&request.get_ref().our_node_name),
+        )?;
         let request = request.into_inner();
         let our_node_name = request.our_node_name;
         let amount_msat = request.amount_msat;
         let description = request.description;
         let expiry_secs = request.expiry_secs;
-        let response = lightning::create_invoice(
-            our_node_name,
+        let response = self.manager.create_invoice(
+            &our_node_name,
             amount_msat as u64,
             &description,
             expiry_secs as u32,
-        )
-        .unwrap();
-        let reply = CreateInvoiceReply { invoice: response };
+        )?;
+        let reply = CreateInvoiceReply {
+            invoice: response.to_string(),
+        };
+        Ok(Response::new(reply))
+    }
+
+    type SubscribeEventsStream = Pin<Box<dyn Stream<Item = Result<NodeEvent, Status>> + Send>>;
+
+    async fn subscribe_events(
+        &self,
+        request: Request<GeneralNodeNameRequest>,
+    ) -> Result<Response<Self::SubscribeEventsStream>, Status> {
+        println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::ReadOnly,
+            Some(&request.get_ref().node_name),
+        )?;
+        let request = request.into_inner();
+        let node_name = request.node_name;
+        let receiver = self.manager.subscribe_events(&node_name)?;
+        #[allow(clippy::result_large_err)]
+        let stream = BroadcastStream::new(receiver).map(|event| match event {
+            Ok(event) => Ok(event),
+            Err(BroadcastStreamRecvError::Lagged(missed)) => {
+                Err(LightningError::EventsLagged(missed).into())
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn replay_events(
+        &self,
+        request: Request<ReplayEventsRequest>,
+    ) -> Result<Response<ReplayEventsReply>, Status> {
+        println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::ReadOnly,
+            Some(&request.get_ref().node_name),
+        )?;
+        let request = request.into_inner();
+        let events = self.manager.replay_events(
+            &request.node_name,
+            request.after_seq,
+            request.limit as usize,
+        )?;
+        let reply = ReplayEventsReply { events };
+        Ok(Response::new(reply))
+    }
+
+    async fn list_payments(
+        &self,
+        request: Request<ListPaymentsRequest>,
+    ) -> Result<Response<ListPaymentsReply>, Status> {
+        println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::ReadOnly,
+            Some(&request.get_ref().our_node_name),
+        )?;
+        let request = request.into_inner();
+        let direction = request
+            .direction
+            .map(|d| {
+                walletrpc::PaymentDirection::try_from(d)
+                    .map_err(|_| LightningError::InvalidPaymentFilter(format!("direction {}", d)))
+            })
+            .transpose()?
+            .map(PaymentDirection::from);
+        let status = request
+            .status
+            .map(|s| {
+                walletrpc::PaymentStatus::try_from(s)
+                    .map_err(|_| LightningError::InvalidPaymentFilter(format!("status {}", s)))
+            })
+            .transpose()?
+            .map(PaymentStatus::from);
+        let (payments, total) = self.manager.list_payments(
+            &request.our_node_name,
+            direction,
+            status,
+            request.since,
+            request.until,
+            request.offset as usize,
+            request.limit as usize,
+        )?;
+        let reply = ListPaymentsReply {
+            payments: payments.into_iter().map(|p| p.into()).collect(),
+            total: total as u32,
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn get_payment(
+        &self,
+        request: Request<GetPaymentRequest>,
+    ) -> Result<Response<Payment>, Status> {
+        println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::ReadOnly,
+            Some(&request.get_ref().our_node_name),
+        )?;
+        let request = request.into_inner();
+        let our_node_name = request.our_node_name;
+        let payment_hash = lightning::parse_payment_hash(&request.payment_hash)?;
+        let response = self.manager.payment(&our_node_name, &payment_hash)?;
+        let reply: Payment = response.into();
         Ok(Response::new(reply))
     }
 
@@ -166,16 +696,19 @@ impl WalletRpcService for MyWallet {
         request: Request<CloseChannelRequest>,
     ) -> Result<Response<GeneralSuccessReply>, Status> {
         println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::Admin,
+            Some(&request.get_ref().our_node_name),
+        )?;
         let request = request.into_inner();
         let our_node_name = request.our_node_name;
-        let node_id = request.node_id;
-        use ldk_node::lightning::ln::ChannelId;
-        let channel_id = request.channel_id.clone().into_bytes()[..]
-            .try_into()
-            .unwrap();
-        let channel_id = ChannelId(channel_id);
-        let response = lightning::close_channel(our_node_name, node_id, channel_id);
-        let reply = GeneralSuccessReply { success: response };
+        let channel_id = lightning::parse_channel_id(&request.channel_id)?;
+        self.manager.require_node(&our_node_name)?;
+        let node_id = lightning::parse_public_key(&request.node_id)?;
+        self.manager
+            .close_channel(&our_node_name, &channel_id, node_id)?;
+        let reply = GeneralSuccessReply { success: true };
         Ok(Response::new(reply))
     }
 
@@ -184,22 +717,28 @@ impl WalletRpcService for MyWallet {
         request: Request<OpenChannelRequest>,
     ) -> Result<Response<GeneralSuccessReply>, Status> {
         println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::Admin,
+            Some(&request.get_ref().our_node_name),
+        )?;
         let request = request.into_inner();
         let our_node_name = request.our_node_name;
-        let node_id = request.node_id;
-        let net_address = request.net_address;
+        self.manager.require_node(&our_node_name)?;
+        let node_id = lightning::parse_public_key(&request.node_id)?;
+        let net_address = lightning::parse_socket_address(&request.net_address)?;
         let channel_amount_sats = request.channel_amount_sats;
         let push_to_counterparty_msat = request.push_to_counterparty_msat;
         let announce_channel = request.announce_channel;
-        let response = lightning::open_channel(
-            our_node_name,
+        self.manager.open_channel(
+            &our_node_name,
             node_id,
             net_address,
             channel_amount_sats as u64,
             push_to_counterparty_msat as u64,
             announce_channel,
-        );
-        let reply = GeneralSuccessReply { success: response };
+        )?;
+        let reply = GeneralSuccessReply { success: true };
         Ok(Response::new(reply))
     }
 
@@ -208,9 +747,14 @@ impl WalletRpcService for MyWallet {
         request: Request<GeneralNodeNameRequest>,
     ) -> Result<Response<ListPeersReply>, Status> {
         println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::ReadOnly,
+            Some(&request.get_ref().node_name),
+        )?;
         let request = request.into_inner();
         let node_name = request.node_name;
-        let response = lightning::list_peers(node_name);
+        let response = self.manager.list_peers(&node_name)?;
         let peers: HashMap<i32, Peer> = response
             .into_iter()
             .enumerate()
@@ -225,9 +769,14 @@ impl WalletRpcService for MyWallet {
         request: Request<GeneralNodeNameRequest>,
     ) -> Result<Response<ListChannelsReply>, Status> {
         println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::ReadOnly,
+            Some(&request.get_ref().node_name),
+        )?;
         let request = request.into_inner();
         let node_name = request.node_name;
-        let response = lightning::list_channels(node_name);
+        let response = self.manager.list_channels(&node_name)?;
         let channels: HashMap<i32, Channel> = response
             .into_iter()
             .enumerate()
@@ -242,11 +791,17 @@ impl WalletRpcService for MyWallet {
         request: Request<DisconnectPeerRequest>,
     ) -> Result<Response<GeneralSuccessReply>, Status> {
         println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::Admin,
+            Some(&request.get_ref().our_node_name),
+        )?;
         let request = request.into_inner();
         let our_node_name = request.our_node_name;
-        let node_id = request.node_id;
-        let response = lightning::disconnect_peer(our_node_name, node_id);
-        let reply = GeneralSuccessReply { success: response };
+        self.manager.require_node(&our_node_name)?;
+        let node_id = lightning::parse_public_key(&request.node_id)?;
+        self.manager.disconnect_peer(&our_node_name, node_id)?;
+        let reply = GeneralSuccessReply { success: true };
         Ok(Response::new(reply))
     }
 
@@ -255,12 +810,19 @@ impl WalletRpcService for MyWallet {
         request: Request<ConnectToPeerRequest>,
     ) -> Result<Response<GeneralSuccessReply>, Status> {
         println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::Admin,
+            Some(&request.get_ref().our_node_name),
+        )?;
         let request = request.into_inner();
         let our_node_name = request.our_node_name;
-        let node_id = request.node_id;
-        let net_address = request.net_address;
-        let response = lightning::connect_to_node(our_node_name, node_id, net_address);
-        let reply = GeneralSuccessReply { success: response };
+        self.manager.require_node(&our_node_name)?;
+        let node_id = lightning::parse_public_key(&request.node_id)?;
+        let net_address = lightning::parse_socket_address(&request.net_address)?;
+        self.manager
+            .connect_to_peer(&our_node_name, node_id, net_address)?;
+        let reply = GeneralSuccessReply { success: true };
         Ok(Response::new(reply))
     }
 
@@ -269,9 +831,14 @@ impl WalletRpcService for MyWallet {
         request: Request<GeneralNodeNameRequest>,
     ) -> Result<Response<GetEsploraAddressReply>, Status> {
         println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::ReadOnly,
+            Some(&request.get_ref().node_name),
+        )?;
         let request = request.into_inner();
         let node_name = request.node_name;
-        let response = lightning::get_esplora_address(node_name);
+        let response = self.manager.esplora_address(&node_name)?;
         let reply = GetEsploraAddressReply { address: response };
         Ok(Response::new(reply))
     }
@@ -281,68 +848,157 @@ impl WalletRpcService for MyWallet {
         request: Request<GeneralNodeNameRequest>,
     ) -> Result<Response<GetNetAddressReply>, Status> {
         println!("Got a request: {:?}", request);
+        auth::authorize(
+            &request,
+            Permission::ReadOnly,
+            Some(&request.get_ref().node_name),
+        )?;
         let request = request.into_inner();
         let node_name = request.node_name;
-        let response = lightning::get_our_address(node_name);
+        let response = self.manager.listening_address(&node_name)?;
         let reply = GetNetAddressReply { address: response };
         Ok(Response::new(reply))
     }
+
+    async fn bake_token(
+        &self,
+        request: Request<BakeTokenRequest>,
+    ) -> Result<Response<BakeTokenReply>, Status> {
+        println!("Got a request: {:?}", request);
+        auth::authorize(&request, Permission::Admin, None)?;
+        let request = request.into_inner();
+        let permission = walletrpc::Permission::try_from(request.permission)
+            .map_err(|_| auth::AuthError::InvalidPermission(request.permission))?;
+        let expires_in_secs = Some(request.expires_in_secs).filter(|secs| *secs > 0);
+        let (id, token) =
+            self.tokens
+                .bake(permission.into(), request.wallet_names, expires_in_secs)?;
+        let reply = BakeTokenReply { id, token };
+        Ok(Response::new(reply))
+    }
+
+    async fn revoke_token(
+        &self,
+        request: Request<RevokeTokenRequest>,
+    ) -> Result<Response<GeneralSuccessReply>, Status> {
+        println!("Got a request: {:?}", request);
+        auth::authorize(&request, Permission::Admin, None)?;
+        let request = request.into_inner();
+        self.tokens.revoke(&request.id)?;
+        let reply = GeneralSuccessReply { success: true };
+        Ok(Response::new(reply))
+    }
+
+    async fn list_tokens(
+        &self,
+        request: Request<ListTokensRequest>,
+    ) -> Result<Response<ListTokensReply>, Status> {
+        println!("Got a request: {:?}", request);
+        auth::authorize(&request, Permission::Admin, None)?;
+        let tokens = self.tokens.list()?.into_iter().map(|t| t.into()).collect();
+        let reply = ListTokensReply { tokens };
+        Ok(Response::new(reply))
+    }
 }
 
-impl From<ChanDetails> for Channel {
-    fn from(chan_details: ChanDetails) -> Self {
-        Channel {
-            channel_id: chan_details.channel_id.to_string(),
-            counterparty_node_id: chan_details.counterparty_node_id.to_string(),
-            channel_value_sats: chan_details.channel_value_sats as i64,
-            balance_msat: chan_details.balance_msat as i64,
-            outbound_capacity_msat: chan_details.outbound_capacity_msat as i64,
-            inbound_capacity_msat: chan_details.inbound_capacity_msat as i64,
-            is_outbound: chan_details.is_outbound,
-            is_channel_ready: chan_details.is_channel_ready,
-            is_usable: chan_details.is_usable,
-            is_public: chan_details.is_public,
+/// Binds a Unix socket only its owner can connect to, replacing the one a previous run
+/// left behind.
+fn bind_unix_socket(path: &Path) -> std::io::Result<UnixListener> {
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if metadata.file_type().is_socket() {
+            std::fs::remove_file(path)?;
         }
     }
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
 }
 
-// impl From<Channel> for ChanDetails {
-//     fn from(channel: Channel) -> Self {
-//         ChanDetails {
-//             channel_id: ChannelId(channel.channel_id),
-//             counterparty_node_id: PublicKey::from_slice(&channel.counterparty_node_id.as_bytes()[..]).unwrap(),
-//             channel_value_sats: channel.channel_value_sats as u64,
-//             balance_msat: channel.balance_msat as u64,
-//             outbound_capacity_msat: channel.outbound_capacity_msat as i64,
-//             inbound_capacity_msat: channel.inbound_capacity_msat as i64,
-//             is_outbound: channel.is_outbound,
-//             is_channel_ready: channel.is_channel_ready,
-//             is_usable: channel.is_usable,
-//             is_public: channel.is_public,
-//         }
-//     }
-// }
-
-
-impl From<WrappedPeerDetails> for Peer {
-    fn from(peer_details: WrappedPeerDetails) -> Self {
-        Peer {
-            address: peer_details.address,
-            node_id: peer_details.node_id.to_string(),
-            is_persisted: peer_details.is_persisted,
-            is_connected: peer_details.is_connected,
-            alias: peer_details.alias,
-        }
+/// Resolves on SIGINT or SIGTERM.
+async fn shutdown_signal() -> std::io::Result<()> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        received = tokio::signal::ctrl_c() => received,
+        _ = terminate.recv() => Ok(()),
     }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let addr = "[::1]:50051".parse()?;
-    let greeter = MyWallet::default();
-    Server::builder()
-        .add_service(WalletRpcServiceServer::new(greeter))
-        .serve(addr)
-        .await?;
-    Ok(())
+    let config = ServerConfig::load()?;
+    if let Some(data_dir) = config.data_dir.clone() {
+        paths::set_project_base_dir(data_dir);
+    }
+    let user_paths = paths::UserPaths::new()?;
+    let tokens = Arc::new(TokenStore::open(&user_paths)?);
+    let manager = Arc::new(NodeManager::new(config.node_defaults));
+    let greeter = MyWallet {
+        tokens: tokens.clone(),
+        manager: manager.clone(),
+    };
+    let authenticator = auth::Authenticator { store: tokens };
+    let service = WalletRpcServiceServer::with_interceptor(greeter, authenticator);
+    let tls_config = tls::server_tls_config(&user_paths, &config.tls)?;
+    let mut listen = config.listen;
+    if config.unix_socket {
+        listen.push(ListenAddress::Unix(user_paths.socket_file().into()));
+    }
+    let (shutdown, shutdown_requested) = watch::channel(());
+    let until_shutdown = |mut shutdown_requested: watch::Receiver<()>| async move {
+        let _ = shutdown_requested.changed().await;
+    };
+    let mut servers = tokio::task::JoinSet::new();
+    let mut unix_sockets = Vec::new();
+    for address in listen {
+        println!("Serving on {}", address);
+        match address {
+            ListenAddress::Tcp(address) => {
+                let router = Server::builder()
+                    .tls_config(tls_config.clone())?
+                    .add_service(service.clone());
+                let until_shutdown = until_shutdown(shutdown_requested.clone());
+                servers.spawn(router.serve_with_shutdown(address, until_shutdown));
+            }
+            // local only, the socket permissions stand in for TLS
+            ListenAddress::Unix(path) => {
+                let incoming = UnixListenerStream::new(bind_unix_socket(&path)?);
+                let router = Server::builder().add_service(service.clone());
+                let until_shutdown = until_shutdown(shutdown_requested.clone());
+                servers.spawn(router.serve_with_incoming_shutdown(incoming, until_shutdown));
+                unix_sockets.push(path);
+            }
+        }
+    }
+    // every listener serves until the first one fails or a signal is received, then they
+    // all stop taking requests while the nodes are stopped
+    let served: Result<(), Box<dyn std::error::Error>> = tokio::select! {
+        Some(result) = servers.join_next() => match result {
+            Ok(served) => served.map_err(Into::into),
+            Err(e) => Err(e.into()),
+        },
+        received = shutdown_signal() => received.map_err(Into::into),
+    };
+    println!("Shutting down");
+    let _ = shutdown.send(());
+    let stopping = async {
+        match tokio::task::spawn_blocking(move || manager.shutdown()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => eprintln!("Unable to stop the nodes: {}", e),
+            Err(e) => eprintln!("Unable to stop the nodes: {}", e),
+        }
+        while servers.join_next().await.is_some() {}
+    };
+    let stopped = tokio::time::timeout(config.shutdown_timeout, stopping).await;
+    for path in unix_sockets {
+        let _ = std::fs::remove_file(path);
+    }
+    if stopped.is_err() {
+        eprintln!(
+            "Nodes or requests still stopping after {:?}, exiting anyway",
+            config.shutdown_timeout
+        );
+        // the runtime would wait for the blocking threads still stopping nodes
+        std::process::exit(1);
+    }
+    served
 }
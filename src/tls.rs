@@ -0,0 +1,126 @@
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+use tonic::transport::{Certificate, Identity, ServerTlsConfig};
+
+use crate::config::TlsPaths;
+use crate::paths::UserPaths;
+
+// home_dir/.bits-wallet/tls.cert
+// home_dir/.bits-wallet/tls.key
+
+/// Environment variables of the `TlsPaths`, see `config::Args`.
+pub const CERT_PATH_VAR: &str = "WALLETRPC_TLS_CERT_PATH";
+pub const KEY_PATH_VAR: &str = "WALLETRPC_TLS_KEY_PATH";
+pub const CLIENT_CA_PATH_VAR: &str = "WALLETRPC_TLS_CLIENT_CA_PATH";
+
+#[derive(Debug)]
+pub enum TlsError {
+    Io(io::Error),
+    /// The self-signed certificate could not be generated.
+    Generate(rcgen::Error),
+    /// Only one of the certificate and its key was given or found.
+    IncompleteIdentity(String),
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TlsError::Io(e) => write!(f, "Unable to read or write TLS files: {}", e),
+            TlsError::Generate(e) => write!(f, "Unable to generate TLS certificate: {}", e),
+            TlsError::IncompleteIdentity(e) => write!(f, "Incomplete TLS identity: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TlsError {}
+
+impl From<io::Error> for TlsError {
+    fn from(e: io::Error) -> Self {
+        TlsError::Io(e)
+    }
+}
+
+impl From<rcgen::Error> for TlsError {
+    fn from(e: rcgen::Error) -> Self {
+        TlsError::Generate(e)
+    }
+}
+
+/// Writes a new file only readable by its owner, failing if it already exists.
+fn write_private(path: &str, contents: &str) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()
+}
+
+/// Generates a self-signed certificate for local connections, like lnd's `tls.cert`.
+fn generate(cert_file: &str, key_file: &str) -> Result<(), TlsError> {
+    let subject_alt_names = vec![
+        "localhost".to_string(),
+        "127.0.0.1".to_string(),
+        "::1".to_string(),
+    ];
+    let cert = rcgen::generate_simple_self_signed(subject_alt_names)?;
+    write_private(key_file, &cert.serialize_private_key_pem())?;
+    write_private(cert_file, &cert.serialize_pem()?)?;
+    Ok(())
+}
+
+fn non_empty(path: &Option<String>) -> Option<String> {
+    path.clone().filter(|path| !path.is_empty())
+}
+
+/// Builds the TLS config of the server. The configured certificate is used if any,
+/// otherwise the one under the project base dir, generated on first run. Mutual TLS is
+/// enabled by configuring a client CA.
+pub fn server_tls_config(
+    user_paths: &UserPaths,
+    paths: &TlsPaths,
+) -> Result<ServerTlsConfig, TlsError> {
+    let (cert_file, key_file) = match (non_empty(&paths.cert_path), non_empty(&paths.key_path)) {
+        (Some(cert_file), Some(key_file)) => (cert_file, key_file),
+        (None, None) => {
+            let cert_file = user_paths.tls_cert_file();
+            let key_file = user_paths.tls_key_file();
+            match (
+                Path::new(&cert_file).exists(),
+                Path::new(&key_file).exists(),
+            ) {
+                (true, true) => {}
+                (false, false) => {
+                    std::fs::create_dir_all(user_paths.project_base_dir())?;
+                    generate(&cert_file, &key_file)?;
+                    println!("Generated TLS certificate {}", cert_file);
+                }
+                _ => {
+                    return Err(TlsError::IncompleteIdentity(format!(
+                        "remove {} and {} to generate new ones",
+                        cert_file, key_file
+                    )))
+                }
+            }
+            (cert_file, key_file)
+        }
+        _ => {
+            return Err(TlsError::IncompleteIdentity(format!(
+                "the certificate and key paths must be set together, {} and {}",
+                CERT_PATH_VAR, KEY_PATH_VAR
+            )))
+        }
+    };
+    let identity = Identity::from_pem(std::fs::read(cert_file)?, std::fs::read(key_file)?);
+    let mut tls_config = ServerTlsConfig::new().identity(identity);
+    if let Some(client_ca_file) = non_empty(&paths.client_ca_path) {
+        let client_ca = Certificate::from_pem(std::fs::read(client_ca_file)?);
+        tls_config = tls_config.client_ca_root(client_ca);
+    }
+    Ok(tls_config)
+}
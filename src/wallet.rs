@@ -1,95 +1,452 @@
-use std::{io::Write, sync::Arc};
+use std::fs::{DirBuilder, OpenOptions};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::{fmt, io::Write, str::FromStr, time::SystemTime};
 
+use argon2::{Algorithm, Argon2, Params, Version};
 use bip39::Mnemonic;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use ldk_node::bitcoin::hashes::hex::FromHex;
 use ldk_node::bitcoin::Network;
+use ldk_node::lightning::ln::msgs::SocketAddress;
+use prost::Message;
 use serde::{Deserialize, Serialize};
+use tonic::{Code, Status};
+use zeroize::{Zeroize, Zeroizing};
 
+use crate::lightning::bytes_to_hex;
 use crate::paths::UserPaths;
-
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
-pub struct NodeConf {
-    pub network: ldk_node::bitcoin::Network,
-    pub storage_dir: String,
-    pub listening_address: String,
-    pub seed: Vec<u8>,
-    pub esplora_address: String,
-}
+use crate::walletrpc::ErrorDetail;
 
 // home_dir/.bits-wallet/wallets/
-// home_dir/.bits-wallet/wallets/wallet_name/seed
+// home_dir/.bits-wallet/wallets/wallet_name/seed (encrypted with the wallet password)
+// home_dir/.bits-wallet/wallets/wallet_name/mnemonic (encrypted with the wallet password)
 // home_dir/.bits-wallet/wallets/wallet_name/config.json
 // home_dir/.bits-wallet/wallets/wallet_name/ldk-data/
+// home_dir/.bits-wallet/archive/wallet_name-timestamp/
 
 pub struct Wallet;
 
+/// The 64 byte BIP39 seed of a wallet. It is wiped from memory when dropped and is
+/// neither `Debug` nor `Serialize`, so that it can't end up in logs.
+#[derive(Clone)]
+pub struct SecretSeed(Zeroizing<Vec<u8>>);
+
+impl SecretSeed {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// The mnemonic of a wallet, wiped from memory when dropped. Like `SecretSeed` it can't be
+/// printed, its words are only handed out by `phrase`.
+pub struct SecretMnemonic(Mnemonic);
+
+impl SecretMnemonic {
+    pub fn parse(phrase: &str) -> Result<Self, bip39::Error> {
+        Mnemonic::parse_normalized(phrase).map(SecretMnemonic)
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.0.word_count()
+    }
+
+    /// The words, separated by spaces.
+    pub fn phrase(&self) -> Zeroizing<String> {
+        Zeroizing::new(self.0.to_string())
+    }
+
+    /// Derives the seed with the BIP39 `passphrase`, empty for none.
+    pub fn to_seed(&self, passphrase: &str) -> SecretSeed {
+        let mut seed = self.0.to_seed_normalized(passphrase);
+        let secret = SecretSeed(Zeroizing::new(seed.to_vec()));
+        seed.zeroize();
+        secret
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WalletConfig {
     wallet_name: String,
+    // configs written before networks were selectable are all testnet
+    #[serde(default = "default_network")]
+    network: Network,
     listening_address: String,
     esplora_address: String,
+    /// Days node events are kept in the event journal, 0 keeps them forever.
+    #[serde(default = "default_event_retention_days")]
+    event_retention_days: u32,
+    #[serde(default)]
+    webhooks: Vec<Webhook>,
+}
+
+/// An endpoint node events are POSTed to, signed with its own secret.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Webhook {
+    pub url: String,
+    pub secret: String,
+}
+
+impl fmt::Debug for Webhook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Webhook")
+            .field("url", &self.url)
+            .field("secret", &"<redacted>")
+            .finish()
+    }
+}
+
+fn default_network() -> Network {
+    Network::Testnet
+}
+
+fn default_event_retention_days() -> u32 {
+    30
+}
+
+#[derive(Debug)]
+pub enum WalletError {
+    /// No wallet with the given name exists.
+    NotFound(String),
+    /// A wallet with the given name already exists.
+    AlreadyExists(String),
+    /// The wallet name is empty or not usable as a directory name.
+    InvalidWalletName(String),
+    InvalidListeningAddress(String),
+    InvalidEsploraAddress(String),
+    /// A wallet file or directory could not be read or written.
+    Io(std::io::Error),
+    /// The mnemonic could not be generated or parsed.
+    Mnemonic(bip39::Error),
+    /// The wallet config file could not be parsed or serialized.
+    InvalidConfig(String),
+    /// The requested network is unknown.
+    InvalidNetwork(String),
+    /// A mainnet wallet was requested without explicitly allowing mainnet.
+    MainnetNotAllowed,
+    InvalidWebhookUrl(String),
+    /// The wallet already has a webhook with the given url.
+    WebhookExists(String),
+    /// The wallet has no webhook with the given url.
+    WebhookNotFound(String),
+    /// The wallet password is empty.
+    InvalidPassword,
+    /// The seed could not be decrypted with the given password.
+    WrongPassword,
+    /// The seed or mnemonic file can't be read, or the seed file is neither encrypted nor
+    /// a plain seed.
+    CorruptSeed(String),
+    /// The wallet was created before its mnemonic was stored.
+    MnemonicNotStored(String),
+    /// The seed could not be encrypted.
+    Encryption(String),
+}
+
+impl WalletError {
+    /// Machine-readable name of the error, sent to clients in `ErrorDetail`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            WalletError::NotFound(_) => "WalletNotFound",
+            WalletError::AlreadyExists(_) => "WalletAlreadyExists",
+            WalletError::InvalidWalletName(_) => "InvalidWalletName",
+            WalletError::InvalidListeningAddress(_) => "InvalidListeningAddress",
+            WalletError::InvalidEsploraAddress(_) => "InvalidEsploraAddress",
+            WalletError::Io(_) => "StorageFailed",
+            WalletError::Mnemonic(_) => "InvalidMnemonic",
+            WalletError::InvalidConfig(_) => "InvalidConfig",
+            WalletError::InvalidNetwork(_) => "InvalidNetwork",
+            WalletError::MainnetNotAllowed => "MainnetNotAllowed",
+            WalletError::InvalidWebhookUrl(_) => "InvalidWebhookUrl",
+            WalletError::WebhookExists(_) => "WebhookExists",
+            WalletError::WebhookNotFound(_) => "WebhookNotFound",
+            WalletError::InvalidPassword => "InvalidPassword",
+            WalletError::WrongPassword => "WrongPassword",
+            WalletError::CorruptSeed(_) => "CorruptSeed",
+            WalletError::MnemonicNotStored(_) => "MnemonicNotStored",
+            WalletError::Encryption(_) => "EncryptionFailed",
+        }
+    }
+
+    pub fn code(&self) -> Code {
+        match self {
+            WalletError::NotFound(_) => Code::NotFound,
+            WalletError::AlreadyExists(_) => Code::AlreadyExists,
+            WalletError::InvalidWalletName(_)
+            | WalletError::InvalidListeningAddress(_)
+            | WalletError::InvalidEsploraAddress(_) => Code::InvalidArgument,
+            WalletError::Io(_) => Code::Internal,
+            WalletError::Mnemonic(_) => Code::InvalidArgument,
+            WalletError::InvalidConfig(_) => Code::FailedPrecondition,
+            WalletError::InvalidNetwork(_) => Code::InvalidArgument,
+            WalletError::MainnetNotAllowed => Code::FailedPrecondition,
+            WalletError::InvalidWebhookUrl(_) => Code::InvalidArgument,
+            WalletError::WebhookExists(_) => Code::AlreadyExists,
+            WalletError::WebhookNotFound(_) => Code::NotFound,
+            WalletError::InvalidPassword => Code::InvalidArgument,
+            WalletError::WrongPassword => Code::PermissionDenied,
+            WalletError::CorruptSeed(_) => Code::DataLoss,
+            WalletError::MnemonicNotStored(_) => Code::NotFound,
+            WalletError::Encryption(_) => Code::Internal,
+        }
+    }
+}
+
+impl fmt::Display for WalletError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WalletError::NotFound(name) => write!(f, "Wallet {} not found", name),
+            WalletError::AlreadyExists(name) => write!(f, "Wallet {} already exists", name),
+            WalletError::InvalidWalletName(name) => write!(f, "Invalid wallet name: {:?}", name),
+            WalletError::InvalidListeningAddress(address) => {
+                write!(f, "Invalid listening address: {:?}", address)
+            }
+            WalletError::InvalidEsploraAddress(address) => {
+                write!(f, "Invalid esplora address: {:?}", address)
+            }
+            WalletError::Io(e) => write!(f, "Wallet storage failed: {}", e),
+            WalletError::Mnemonic(e) => write!(f, "Mnemonic error: {}", e),
+            WalletError::InvalidConfig(e) => write!(f, "Invalid wallet config: {}", e),
+            WalletError::InvalidNetwork(e) => write!(f, "Invalid network: {}", e),
+            WalletError::MainnetNotAllowed => {
+                write!(f, "Mainnet wallets must be explicitly allowed")
+            }
+            WalletError::InvalidWebhookUrl(url) => write!(f, "Invalid webhook url: {:?}", url),
+            WalletError::WebhookExists(url) => write!(f, "Webhook {} already exists", url),
+            WalletError::WebhookNotFound(url) => write!(f, "Webhook {} not found", url),
+            WalletError::InvalidPassword => write!(f, "The wallet password must not be empty"),
+            WalletError::WrongPassword => write!(f, "Wrong wallet password"),
+            WalletError::CorruptSeed(e) => write!(f, "Corrupt wallet secret: {}", e),
+            WalletError::MnemonicNotStored(name) => {
+                write!(f, "The mnemonic of wallet {} was not stored", name)
+            }
+            WalletError::Encryption(e) => write!(f, "Seed encryption failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for WalletError {}
+
+impl From<WalletError> for Status {
+    fn from(e: WalletError) -> Self {
+        let detail = ErrorDetail {
+            kind: e.kind().to_string(),
+        };
+        Status::with_details(e.code(), e.to_string(), detail.encode_to_vec().into())
+    }
+}
+
+impl From<std::io::Error> for WalletError {
+    fn from(e: std::io::Error) -> Self {
+        WalletError::Io(e)
+    }
+}
+
+impl From<bip39::Error> for WalletError {
+    fn from(e: bip39::Error) -> Self {
+        WalletError::Mnemonic(e)
+    }
+}
+
+pub fn validate_password(password: &str) -> Result<(), WalletError> {
+    if password.is_empty() {
+        return Err(WalletError::InvalidPassword);
+    }
+    Ok(())
+}
+
+pub fn validate_wallet_name(wallet_name: &str) -> Result<(), WalletError> {
+    if wallet_name.is_empty() || wallet_name.starts_with('.') || wallet_name.contains(['/', '\\']) {
+        return Err(WalletError::InvalidWalletName(wallet_name.to_string()));
+    }
+    Ok(())
+}
+
+pub fn validate_listening_address(listening_address: &str) -> Result<(), WalletError> {
+    match SocketAddress::from_str(listening_address) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(WalletError::InvalidListeningAddress(
+            listening_address.to_string(),
+        )),
+    }
+}
+
+fn is_http_url(url: &str) -> bool {
+    let host = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"));
+    matches!(host, Some(host) if !host.is_empty())
+}
+
+pub fn validate_esplora_address(esplora_address: &str) -> Result<(), WalletError> {
+    if !is_http_url(esplora_address) {
+        return Err(WalletError::InvalidEsploraAddress(
+            esplora_address.to_string(),
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_webhook_url(url: &str) -> Result<(), WalletError> {
+    if !is_http_url(url) {
+        return Err(WalletError::InvalidWebhookUrl(url.to_string()));
+    }
+    Ok(())
+}
+
+/// A secret encrypted with XChaCha20-Poly1305 under a key derived from the wallet password
+/// with Argon2id. The KDF parameters are stored so that they can be raised later on.
+#[derive(Serialize, Deserialize)]
+struct Sealed {
+    /// Argon2id memory cost in KiB.
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    salt: String,
+    nonce: String,
+    /// The encrypted secret followed by its authentication tag.
+    ciphertext: String,
+}
+
+fn seal_key(
+    password: &str,
+    salt: &[u8],
+    params: Params,
+) -> Result<Zeroizing<[u8; 32]>, WalletError> {
+    let mut key = Zeroizing::new([0; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), salt, key.as_mut_slice())
+        .map_err(|e| WalletError::Encryption(e.to_string()))?;
+    Ok(key)
+}
+
+impl Sealed {
+    fn seal(password: &str, secret: &[u8]) -> Result<Self, WalletError> {
+        let params = Params::default();
+        let salt = rand::random::<[u8; 16]>();
+        let nonce = rand::random::<[u8; 24]>();
+        let key = seal_key(password, &salt, params.clone())?;
+        let ciphertext = XChaCha20Poly1305::new(Key::from_slice(key.as_slice()))
+            .encrypt(XNonce::from_slice(&nonce), secret)
+            .map_err(|e| WalletError::Encryption(e.to_string()))?;
+        Ok(Sealed {
+            m_cost: params.m_cost(),
+            t_cost: params.t_cost(),
+            p_cost: params.p_cost(),
+            salt: bytes_to_hex(&salt),
+            nonce: bytes_to_hex(&nonce),
+            ciphertext: bytes_to_hex(&ciphertext),
+        })
+    }
+
+    fn open(&self, password: &str) -> Result<Zeroizing<Vec<u8>>, WalletError> {
+        let corrupt = |e: &dyn fmt::Display| WalletError::CorruptSeed(e.to_string());
+        let params =
+            Params::new(self.m_cost, self.t_cost, self.p_cost, None).map_err(|e| corrupt(&e))?;
+        let salt = Vec::<u8>::from_hex(&self.salt).map_err(|e| corrupt(&e))?;
+        let nonce = <[u8; 24]>::from_hex(&self.nonce).map_err(|e| corrupt(&e))?;
+        let ciphertext = Vec::<u8>::from_hex(&self.ciphertext).map_err(|e| corrupt(&e))?;
+        let key = seal_key(password, &salt, params)?;
+        XChaCha20Poly1305::new(Key::from_slice(key.as_slice()))
+            .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+            .map(Zeroizing::new)
+            .map_err(|_| WalletError::WrongPassword)
+    }
+}
+
+/// Replaces a file with one only readable by its owner.
+fn write_private(path: &str, contents: &[u8]) -> std::io::Result<()> {
+    let tmp_path = format!("{}.tmp", path);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    std::fs::rename(tmp_path, path)
+}
+
+fn write_sealed(path: &str, secret: &[u8], password: &str) -> Result<(), WalletError> {
+    let sealed = Sealed::seal(password, secret)?;
+    let sealed =
+        serde_json::to_vec_pretty(&sealed).map_err(|e| WalletError::Encryption(e.to_string()))?;
+    write_private(path, &sealed)?;
+    Ok(())
+}
+
+/// Reads a wallet file, `None` if it doesn't exist.
+fn read_wallet_file(path: &str) -> Result<Option<Vec<u8>>, WalletError> {
+    match std::fs::read(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Creates a directory only accessible by its owner, failing if it already exists.
+fn create_private_dir(path: &str) -> std::io::Result<()> {
+    DirBuilder::new().mode(0o700).create(path)
 }
 
 impl WalletConfig {
-    pub fn new(wallet_name: &str) -> anyhow::Result<Self> {
-        let config_file = UserPaths::new().config_file(wallet_name);
+    /// Loads the config of an existing wallet.
+    pub fn new(wallet_name: &str) -> Result<Self, WalletError> {
+        validate_wallet_name(wallet_name)?;
+        let config_file = UserPaths::new()?.config_file(wallet_name);
         let config_file = match std::fs::read(config_file) {
             Ok(s) => s,
-            Err(_e) => {
-                dbg!("No config file found for wallet {}", wallet_name);
-                dbg!("Creating new config file for wallet {}", wallet_name);
-                let config = WalletConfig {
-                    wallet_name: wallet_name.to_string(),
-                    listening_address: "".to_string(),
-                    esplora_address: "".to_string(),
-                };
-                config.write();
-
-                return Ok(config);
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(WalletError::NotFound(wallet_name.to_string()))
             }
+            Err(e) => return Err(e.into()),
         };
         let config: WalletConfig = match serde_json::from_slice(&config_file) {
             Ok(c) => c,
             Err(e) => {
-                return Err(anyhow::anyhow!(
+                return Err(WalletError::InvalidConfig(format!(
                     "Failed to parse config file for wallet {}: {}",
-                    wallet_name,
-                    e
-                ))
+                    wallet_name, e
+                )))
             }
         };
         Ok(config)
     }
-    pub fn update(&mut self, listening_address: String, esplora_address: String) -> bool {
+    pub fn update(
+        &mut self,
+        listening_address: String,
+        esplora_address: String,
+        event_retention_days: Option<u32>,
+    ) -> Result<(), WalletError> {
         self.listening_address = listening_address;
         self.esplora_address = esplora_address;
+        if let Some(event_retention_days) = event_retention_days {
+            self.event_retention_days = event_retention_days;
+        }
         self.write()
     }
-    fn write(&self) -> bool {
-        let config_file = UserPaths::new().config_file(&self.wallet_name);
-        let mut config_file = match std::fs::File::create(config_file) {
-            Ok(file) => file,
-            Err(_) => return false,
-        };
-        let pretty_json = match serde_json::to_string_pretty(&self) {
-            Ok(s) => s,
-            Err(_) => return false,
-        };
-        match config_file.write_all(pretty_json.as_bytes()) {
-            Ok(_) => {}
-            Err(_) => return false,
-        };
-        match config_file.sync_all() {
-            Ok(_) => true,
-            Err(_) => false,
-        }
+    fn write(&self) -> Result<(), WalletError> {
+        let config_file = UserPaths::new()?.config_file(&self.wallet_name);
+        // webhook secrets are kept in the config
+        let mut config_file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(config_file)?;
+        let pretty_json = serde_json::to_string_pretty(&self)
+            .map_err(|e| WalletError::InvalidConfig(e.to_string()))?;
+        config_file.write_all(pretty_json.as_bytes())?;
+        config_file.sync_all()?;
+        Ok(())
     }
-    // set listening address
-    fn listening_address(&mut self, listening_address: String) {
-        self.listening_address = listening_address;
+    // get wallet name
+    pub fn get_wallet_name(&self) -> String {
+        self.wallet_name.clone()
     }
-    // set esplora address
-    fn esplora_address(&mut self, esplora_address: String) {
-        self.esplora_address = esplora_address;
+    // get network
+    pub fn get_network(&self) -> Network {
+        self.network
     }
     // get listening address
     pub fn get_listening_address(&self) -> String {
@@ -99,66 +456,279 @@ impl WalletConfig {
     pub fn get_esplora_address(&self) -> String {
         self.esplora_address.clone()
     }
+    // get event retention
+    pub fn get_event_retention_days(&self) -> u32 {
+        self.event_retention_days
+    }
+    // get webhooks
+    pub fn get_webhooks(&self) -> Vec<Webhook> {
+        self.webhooks.clone()
+    }
+    /// Adds a webhook with a new random secret, which is returned.
+    pub fn add_webhook(&mut self, url: String) -> Result<String, WalletError> {
+        validate_webhook_url(&url)?;
+        if self.webhooks.iter().any(|w| w.url == url) {
+            return Err(WalletError::WebhookExists(url));
+        }
+        let secret = bytes_to_hex(&rand::random::<[u8; 32]>());
+        self.webhooks.push(Webhook {
+            url,
+            secret: secret.clone(),
+        });
+        self.write()?;
+        Ok(secret)
+    }
+    pub fn remove_webhook(&mut self, url: &str) -> Result<(), WalletError> {
+        let count = self.webhooks.len();
+        self.webhooks.retain(|w| w.url != url);
+        if self.webhooks.len() == count {
+            return Err(WalletError::WebhookNotFound(url.to_string()));
+        }
+        self.write()
+    }
 }
 
 impl Wallet {
+    /// Creates a wallet with a new 12 word mnemonic, its seed is derived with the optional
+    /// BIP39 `passphrase`. The passphrase isn't stored, it's needed again to restore.
+    #[allow(clippy::new_ret_no_self, clippy::too_many_arguments)]
     pub fn new(
         network: Network,
+        allow_mainnet: bool,
         wallet_name: &str,
         listening_address: &str,
         esplora_address: &str,
-    ) -> anyhow::Result<Mnemonic> {
-        let mnemonic = Mnemonic::generate(12).unwrap();
-        let seed = mnemonic.to_seed_normalized("");
-        let project_base_dir = UserPaths::new().project_base_dir();
-        let wallet_dir = UserPaths::new().wallet_dir(&wallet_name);
-        let seed_file = UserPaths::new().seed_file(&wallet_name);
-        std::fs::create_dir_all(&project_base_dir).unwrap();
-        std::fs::create_dir_all(&wallet_dir).unwrap();
-        let mut seed_file = std::fs::File::create(seed_file).unwrap();
-        seed_file.write_all(&seed).unwrap();
-        seed_file.sync_all().unwrap();
-        let config = WalletConfig::new(&wallet_name)?;
-        config.write();
-
-        let ldk_data_dir = format!("{}/ldk-data", wallet_dir);
-        std::fs::create_dir_all(&ldk_data_dir).unwrap();
-        let node_conf = Arc::new(NodeConf {
+        passphrase: &str,
+        password: &str,
+    ) -> Result<SecretMnemonic, WalletError> {
+        let mnemonic = SecretMnemonic(Mnemonic::generate(12)?);
+        Wallet::create(
             network,
-            storage_dir: ldk_data_dir,
-            listening_address: listening_address.to_string(),
-            seed: seed.to_vec(),
-            esplora_address: esplora_address.to_string(),
-        });
-        // if lightning::init_lazy(node_conf.clone()).0 {
-        //     dbg!("Lightning node initialized");
-        // } else {
-        //     dbg!("Lightning node failed to initialize");
-        // }
+            allow_mainnet,
+            wallet_name,
+            listening_address,
+            esplora_address,
+            &mnemonic,
+            passphrase,
+            password,
+        )?;
         Ok(mnemonic)
     }
 
-    fn list_wallets() -> Vec<String> {
-        let wallets_dir = UserPaths::new().wallets_dir();
+    /// Recreates a wallet from its 12 or 24 word mnemonic. On-chain funds are found again
+    /// by the wallet sync once the node is started.
+    #[allow(clippy::too_many_arguments)]
+    pub fn restore(
+        network: Network,
+        allow_mainnet: bool,
+        wallet_name: &str,
+        listening_address: &str,
+        esplora_address: &str,
+        mnemonic: &str,
+        passphrase: &str,
+        password: &str,
+    ) -> Result<(), WalletError> {
+        let mnemonic = SecretMnemonic::parse(mnemonic)?;
+        if mnemonic.word_count() != 12 && mnemonic.word_count() != 24 {
+            return Err(bip39::Error::BadWordCount(mnemonic.word_count()).into());
+        }
+        Wallet::create(
+            network,
+            allow_mainnet,
+            wallet_name,
+            listening_address,
+            esplora_address,
+            &mnemonic,
+            passphrase,
+            password,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create(
+        network: Network,
+        allow_mainnet: bool,
+        wallet_name: &str,
+        listening_address: &str,
+        esplora_address: &str,
+        mnemonic: &SecretMnemonic,
+        passphrase: &str,
+        password: &str,
+    ) -> Result<(), WalletError> {
+        if network == Network::Bitcoin && !allow_mainnet {
+            return Err(WalletError::MainnetNotAllowed);
+        }
+        validate_wallet_name(wallet_name)?;
+        validate_listening_address(listening_address)?;
+        validate_esplora_address(esplora_address)?;
+        validate_password(password)?;
+        let user_paths = UserPaths::new()?;
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(user_paths.wallets_dir())?;
+        // create_dir fails if the wallet exists, so an existing seed is never overwritten
+        let wallet_dir = user_paths.wallet_dir(wallet_name);
+        match create_private_dir(&wallet_dir) {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                return Err(WalletError::AlreadyExists(wallet_name.to_string()))
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        let created = (|| {
+            let seed = mnemonic.to_seed(passphrase);
+            write_sealed(
+                &user_paths.seed_file(wallet_name),
+                seed.as_bytes(),
+                password,
+            )?;
+            write_sealed(
+                &user_paths.mnemonic_file(wallet_name),
+                mnemonic.phrase().as_bytes(),
+                password,
+            )?;
+            let config = WalletConfig {
+                wallet_name: wallet_name.to_string(),
+                network,
+                listening_address: listening_address.to_string(),
+                esplora_address: esplora_address.to_string(),
+                event_retention_days: default_event_retention_days(),
+                webhooks: Vec::new(),
+            };
+            config.write()?;
+            create_private_dir(&user_paths.ldk_data_dir(wallet_name))?;
+            Ok(())
+        })();
+        // a half written wallet would block the name for good
+        if created.is_err() {
+            let _ = std::fs::remove_dir_all(&wallet_dir);
+        }
+        created
+    }
+
+    /// Decrypts the seed of the wallet. Seeds written before they were encrypted are
+    /// encrypted with the given password first.
+    pub fn unlock(wallet_name: &str, password: &str) -> Result<SecretSeed, WalletError> {
+        validate_wallet_name(wallet_name)?;
+        let seed_file = UserPaths::new()?.seed_file(wallet_name);
+        let contents = read_wallet_file(&seed_file)?
+            .map(Zeroizing::new)
+            .ok_or_else(|| WalletError::NotFound(wallet_name.to_string()))?;
+        match serde_json::from_slice::<Sealed>(&contents) {
+            Ok(sealed) => sealed.open(password).map(SecretSeed),
+            Err(_) if contents.len() == 64 => {
+                validate_password(password)?;
+                write_sealed(&seed_file, &contents, password)?;
+                Ok(SecretSeed(contents))
+            }
+            Err(e) => Err(WalletError::CorruptSeed(e.to_string())),
+        }
+    }
+
+    /// Decrypts the stored mnemonic, `None` for wallets created before it was stored.
+    fn open_mnemonic(
+        wallet_name: &str,
+        password: &str,
+    ) -> Result<Option<SecretMnemonic>, WalletError> {
+        let mnemonic_file = UserPaths::new()?.mnemonic_file(wallet_name);
+        let contents = match read_wallet_file(&mnemonic_file)? {
+            Some(contents) => contents,
+            None => return Ok(None),
+        };
+        let sealed: Sealed = serde_json::from_slice(&contents)
+            .map_err(|e| WalletError::CorruptSeed(e.to_string()))?;
+        let mnemonic = sealed.open(password)?;
+        let mnemonic =
+            std::str::from_utf8(&mnemonic).map_err(|e| WalletError::CorruptSeed(e.to_string()))?;
+        Ok(Some(SecretMnemonic::parse(mnemonic)?))
+    }
+
+    /// Decrypts the mnemonic of the wallet with its password. The BIP39 passphrase, if
+    /// any, is not stored.
+    pub fn export_mnemonic(
+        wallet_name: &str,
+        password: &str,
+    ) -> Result<SecretMnemonic, WalletError> {
+        // the seed is checked first so that a wrong password is reported as such
+        Wallet::unlock(wallet_name, password)?;
+        Wallet::open_mnemonic(wallet_name, password)?
+            .ok_or_else(|| WalletError::MnemonicNotStored(wallet_name.to_string()))
+    }
+
+    /// Encrypts the seed and mnemonic again with a new password, the old one must be given.
+    pub fn change_password(
+        wallet_name: &str,
+        old_password: &str,
+        new_password: &str,
+    ) -> Result<(), WalletError> {
+        validate_password(new_password)?;
+        let seed = Wallet::unlock(wallet_name, old_password)?;
+        let mnemonic = Wallet::open_mnemonic(wallet_name, old_password)?;
+        let user_paths = UserPaths::new()?;
+        if let Some(mnemonic) = mnemonic {
+            write_sealed(
+                &user_paths.mnemonic_file(wallet_name),
+                mnemonic.phrase().as_bytes(),
+                new_password,
+            )?;
+        }
+        write_sealed(
+            &user_paths.seed_file(wallet_name),
+            seed.as_bytes(),
+            new_password,
+        )
+    }
+
+    pub fn list_wallets() -> Result<Vec<String>, WalletError> {
+        let wallets_dir = UserPaths::new()?.wallets_dir();
         let mut wallets = Vec::new();
-        for entry in std::fs::read_dir(wallets_dir).unwrap() {
-            let entry = entry.unwrap();
-            let path = entry.path();
+        let entries = match std::fs::read_dir(wallets_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(wallets),
+            Err(e) => return Err(e.into()),
+        };
+        for entry in entries {
+            let path = entry?.path();
             if path.is_dir() {
-                let wallet_name = path.file_name().unwrap().to_str().unwrap().to_string();
-                wallets.push(wallet_name);
+                if let Some(wallet_name) = path.file_name().and_then(|n| n.to_str()) {
+                    wallets.push(wallet_name.to_string());
+                }
             }
         }
-        wallets
+        wallets.sort();
+        Ok(wallets)
     }
-    fn update_config(
+
+    /// Validates and saves new settings of an existing wallet, picked up by its node on
+    /// the next start.
+    pub fn update_config(
         wallet_name: String,
         esplora_address: String,
         listening_address: String,
-    ) -> bool {
-        match WalletConfig::new(&wallet_name) {
-            Ok(mut config) => config.update(listening_address, esplora_address),
-            Err(_) => false,
+        event_retention_days: Option<u32>,
+    ) -> Result<(), WalletError> {
+        validate_listening_address(&listening_address)?;
+        validate_esplora_address(&esplora_address)?;
+        let mut config = WalletConfig::new(&wallet_name)?;
+        config.update(listening_address, esplora_address, event_retention_days)
+    }
+    /// Moves the wallet directory, seed included, into the archive instead of deleting it.
+    pub fn archive(wallet_name: &str) -> Result<String, WalletError> {
+        validate_wallet_name(wallet_name)?;
+        let user_paths = UserPaths::new()?;
+        let wallet_dir = user_paths.wallet_dir(wallet_name);
+        if !std::path::Path::new(&wallet_dir).is_dir() {
+            return Err(WalletError::NotFound(wallet_name.to_string()));
         }
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let archive_dir = format!("{}/{}-{}", user_paths.archive_dir(), wallet_name, timestamp);
+        std::fs::create_dir_all(user_paths.archive_dir())?;
+        std::fs::rename(wallet_dir, &archive_dir)?;
+        Ok(archive_dir)
     }
 }
@@ -0,0 +1,838 @@
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::time::Duration;
+
+use ldk_node::bitcoin::address::NetworkUnchecked;
+use ldk_node::bitcoin::hashes::hex::FromHex;
+use ldk_node::bitcoin::secp256k1::PublicKey;
+use ldk_node::bitcoin::{Address, Amount, Txid};
+use ldk_node::lightning::ln::{ChannelId, PaymentHash, PaymentPreimage, PaymentSecret};
+use ldk_node::lightning_invoice::Bolt11Invoice;
+use prost::Message;
+use tonic::metadata::MetadataValue;
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Uri};
+use tonic::{Code, Request, Response, Status, Streaming};
+
+use crate::lightning::bytes_to_hex;
+use crate::wallet::SecretMnemonic;
+use crate::walletrpc::wallet_rpc_service_client::WalletRpcServiceClient;
+use crate::walletrpc::{
+    self, ChangePasswordRequest, ConnectToPeerRequest, CreateInvoiceRequest, DisconnectPeerRequest,
+    ErrorDetail, ExportMnemonicRequest, GeneralNodeNameRequest, GetPaymentRequest,
+    GetWalletConfigReply, ListPaymentsRequest, ListWalletsRequest, NodeEvent, OpenChannelRequest,
+    PayInvoiceRequest, PaymentDirection, PaymentStatus, SendToAddressRequest, SweepAllRequest,
+    UnlockWalletRequest,
+};
+
+/// Prefix of server addresses that are Unix socket paths.
+pub const UNIX_PREFIX: &str = "unix:";
+
+/// The generated client, for the RPCs `WalletClient` doesn't wrap.
+pub type RawClient = WalletRpcServiceClient<InterceptedService<Channel, Bearer>>;
+
+/// Sends the token, if any, as `authorization: Bearer <token>`. Works with any client
+/// generated from the proto, `RawClient` or one compiled separately.
+#[derive(Clone)]
+pub struct Bearer(Option<String>);
+
+impl Bearer {
+    pub fn new(token: Option<String>) -> Self {
+        Bearer(token)
+    }
+}
+
+impl Interceptor for Bearer {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(token) = &self.0 {
+            let value = MetadataValue::try_from(format!("Bearer {}", token))
+                .map_err(|e| Status::invalid_argument(e.to_string()))?;
+            request.metadata_mut().insert("authorization", value);
+        }
+        Ok(request)
+    }
+}
+
+/// Where and how to reach the server.
+#[derive(Clone)]
+pub struct ClientConfig {
+    server: String,
+    tls_ca_certificate: Option<Vec<u8>>,
+    tls_domain: Option<String>,
+    token: Option<String>,
+    connect_timeout: Duration,
+    timeout: Option<Duration>,
+    max_retries: u32,
+    retry_backoff: Duration,
+}
+
+impl ClientConfig {
+    /// `server` is an `https://` or `http://` url, or `unix:<path>` for a Unix socket.
+    pub fn new(server: impl Into<String>) -> Self {
+        ClientConfig {
+            server: server.into(),
+            tls_ca_certificate: None,
+            tls_domain: None,
+            token: None,
+            connect_timeout: Duration::from_secs(10),
+            timeout: None,
+            max_retries: 3,
+            retry_backoff: Duration::from_millis(200),
+        }
+    }
+
+    /// PEM certificate the server's one must be signed by, or be. Required for `https://`.
+    pub fn tls_ca_certificate(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.tls_ca_certificate = Some(pem.into());
+        self
+    }
+
+    /// Name the certificate of the server is checked against, the server host by default.
+    pub fn tls_domain(mut self, domain: impl Into<String>) -> Self {
+        self.tls_domain = Some(domain.into());
+        self
+    }
+
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Deadline of each call, none by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// How often idempotent calls are retried while the server is unavailable, waiting
+    /// `backoff` times the attempt number in between.
+    pub fn retries(mut self, max_retries: u32, backoff: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_backoff = backoff;
+        self
+    }
+
+    fn endpoint(&self) -> Result<Endpoint, ClientError> {
+        let uri: Uri = match self.server.strip_prefix(UNIX_PREFIX) {
+            // the uri is ignored by the connector
+            Some(_) => Uri::from_static("http://[::1]:50051"),
+            None => self
+                .server
+                .parse()
+                .map_err(|_| ClientError::InvalidServer(self.server.clone()))?,
+        };
+        let mut endpoint = Endpoint::from(uri.clone()).connect_timeout(self.connect_timeout);
+        if let Some(timeout) = self.timeout {
+            endpoint = endpoint.timeout(timeout);
+        }
+        if uri.scheme_str() == Some("https") {
+            let pem = self
+                .tls_ca_certificate
+                .clone()
+                .ok_or(ClientError::MissingCertificate)?;
+            let domain = match &self.tls_domain {
+                Some(domain) => domain.clone(),
+                None => uri
+                    .host()
+                    .unwrap_or_default()
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .to_string(),
+            };
+            let tls = ClientTlsConfig::new()
+                .ca_certificate(Certificate::from_pem(pem))
+                .domain_name(domain);
+            endpoint = endpoint.tls_config(tls)?;
+        }
+        Ok(endpoint)
+    }
+}
+
+#[derive(Debug)]
+pub enum ClientError {
+    InvalidServer(String),
+    /// `https://` servers need `ClientConfig::tls_ca_certificate`.
+    MissingCertificate,
+    Transport(tonic::transport::Error),
+    /// The server refused the call.
+    Status(Box<Status>),
+    /// The server replied with a field that doesn't parse.
+    InvalidReply(String),
+}
+
+impl ClientError {
+    /// The `ErrorDetail` kind the server sent, or the name of the client-side failure.
+    pub fn kind(&self) -> String {
+        match self {
+            ClientError::InvalidServer(_) => "InvalidServer".to_string(),
+            ClientError::MissingCertificate => "MissingCertificate".to_string(),
+            ClientError::Transport(_) => "TransportFailed".to_string(),
+            ClientError::Status(status) => ErrorDetail::decode(status.details())
+                .map(|detail| detail.kind)
+                .unwrap_or_default(),
+            ClientError::InvalidReply(_) => "InvalidReply".to_string(),
+        }
+    }
+
+    pub fn code(&self) -> Code {
+        match self {
+            ClientError::InvalidServer(_) | ClientError::MissingCertificate => {
+                Code::InvalidArgument
+            }
+            ClientError::Transport(_) => Code::Unavailable,
+            ClientError::Status(status) => status.code(),
+            ClientError::InvalidReply(_) => Code::Internal,
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientError::InvalidServer(server) => write!(f, "Invalid server: {:?}", server),
+            ClientError::MissingCertificate => {
+                write!(f, "The certificate of an https server is required")
+            }
+            ClientError::Transport(e) => write!(f, "Unable to connect: {}", e),
+            ClientError::Status(status) => {
+                write!(
+                    f,
+                    "{:?} {}: {}",
+                    status.code(),
+                    self.kind(),
+                    status.message()
+                )
+            }
+            ClientError::InvalidReply(e) => write!(f, "Invalid reply: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<tonic::transport::Error> for ClientError {
+    fn from(e: tonic::transport::Error) -> Self {
+        ClientError::Transport(e)
+    }
+}
+
+impl From<Status> for ClientError {
+    fn from(status: Status) -> Self {
+        ClientError::Status(Box::new(status))
+    }
+}
+
+fn parse<T: FromStr>(field: &str, value: &str) -> Result<T, ClientError> {
+    T::from_str(value).map_err(|_| ClientError::InvalidReply(format!("{}: {:?}", field, value)))
+}
+
+fn parse_bytes<const N: usize>(field: &str, value: &str) -> Result<[u8; N], ClientError>
+where
+    [u8; N]: FromHex,
+{
+    <[u8; N]>::from_hex(value)
+        .map_err(|_| ClientError::InvalidReply(format!("{}: {:?}", field, value)))
+}
+
+/// Like `parse_bytes`, `None` for the empty strings of unknown values.
+fn parse_optional_bytes<const N: usize>(
+    field: &str,
+    value: &str,
+) -> Result<Option<[u8; N]>, ClientError>
+where
+    [u8; N]: FromHex,
+{
+    match value {
+        "" => Ok(None),
+        value => parse_bytes(field, value).map(Some),
+    }
+}
+
+fn amount_msat(field: &str, msat: i64) -> Result<u64, ClientError> {
+    u64::try_from(msat).map_err(|_| ClientError::InvalidReply(format!("{}: {}", field, msat)))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelInfo {
+    pub channel_id: ChannelId,
+    pub user_channel_id: u128,
+    pub counterparty_node_id: PublicKey,
+    pub channel_value: Amount,
+    pub balance_msat: u64,
+    pub outbound_capacity_msat: u64,
+    pub inbound_capacity_msat: u64,
+    pub is_outbound: bool,
+    pub is_channel_ready: bool,
+    pub is_usable: bool,
+    pub is_public: bool,
+}
+
+impl TryFrom<walletrpc::Channel> for ChannelInfo {
+    type Error = ClientError;
+
+    fn try_from(channel: walletrpc::Channel) -> Result<Self, Self::Error> {
+        Ok(ChannelInfo {
+            channel_id: ChannelId(parse_bytes("channel_id", &channel.channel_id)?),
+            user_channel_id: u128::from_be_bytes(parse_bytes(
+                "user_channel_id",
+                &channel.user_channel_id,
+            )?),
+            counterparty_node_id: parse("counterparty_node_id", &channel.counterparty_node_id)?,
+            channel_value: Amount::from_sat(amount_msat(
+                "channel_value_sats",
+                channel.channel_value_sats,
+            )?),
+            balance_msat: amount_msat("balance_msat", channel.balance_msat)?,
+            outbound_capacity_msat: amount_msat(
+                "outbound_capacity_msat",
+                channel.outbound_capacity_msat,
+            )?,
+            inbound_capacity_msat: amount_msat(
+                "inbound_capacity_msat",
+                channel.inbound_capacity_msat,
+            )?,
+            is_outbound: channel.is_outbound,
+            is_channel_ready: channel.is_channel_ready,
+            is_usable: channel.is_usable,
+            is_public: channel.is_public,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerInfo {
+    pub node_id: PublicKey,
+    pub address: String,
+    pub is_persisted: bool,
+    pub is_connected: bool,
+    pub alias: String,
+}
+
+impl TryFrom<walletrpc::Peer> for PeerInfo {
+    type Error = ClientError;
+
+    fn try_from(peer: walletrpc::Peer) -> Result<Self, Self::Error> {
+        Ok(PeerInfo {
+            node_id: parse("node_id", &peer.node_id)?,
+            address: peer.address,
+            is_persisted: peer.is_persisted,
+            is_connected: peer.is_connected,
+            alias: peer.alias,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelBalance {
+    pub channel_id: ChannelId,
+    pub counterparty_node_id: PublicKey,
+    pub balance_msat: u64,
+    pub outbound_capacity_msat: u64,
+    pub inbound_capacity_msat: u64,
+    pub is_usable: bool,
+}
+
+impl TryFrom<walletrpc::ChannelBalance> for ChannelBalance {
+    type Error = ClientError;
+
+    fn try_from(balance: walletrpc::ChannelBalance) -> Result<Self, Self::Error> {
+        Ok(ChannelBalance {
+            channel_id: ChannelId(parse_bytes("channel_id", &balance.channel_id)?),
+            counterparty_node_id: parse("counterparty_node_id", &balance.counterparty_node_id)?,
+            balance_msat: balance.balance_msat,
+            outbound_capacity_msat: balance.outbound_capacity_msat,
+            inbound_capacity_msat: balance.inbound_capacity_msat,
+            is_usable: balance.is_usable,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Balances {
+    pub total_onchain: Amount,
+    pub spendable_onchain: Amount,
+    pub total_lightning_msat: u64,
+    pub outbound_capacity_msat: u64,
+    pub inbound_capacity_msat: u64,
+    pub channels: Vec<ChannelBalance>,
+}
+
+impl TryFrom<walletrpc::GetBalancesReply> for Balances {
+    type Error = ClientError;
+
+    fn try_from(reply: walletrpc::GetBalancesReply) -> Result<Self, Self::Error> {
+        Ok(Balances {
+            total_onchain: Amount::from_sat(reply.total_onchain_balance_sats),
+            spendable_onchain: Amount::from_sat(reply.spendable_onchain_balance_sats),
+            total_lightning_msat: reply.total_lightning_balance_msat,
+            outbound_capacity_msat: reply.outbound_capacity_msat,
+            inbound_capacity_msat: reply.inbound_capacity_msat,
+            channels: reply
+                .channels
+                .into_iter()
+                .map(ChannelBalance::try_from)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentInfo {
+    pub hash: PaymentHash,
+    pub preimage: Option<PaymentPreimage>,
+    pub secret: Option<PaymentSecret>,
+    pub amount_msat: Option<u64>,
+    pub direction: PaymentDirection,
+    pub status: PaymentStatus,
+    /// Seconds since the Unix epoch, unset for pending payments and pruned events.
+    pub timestamp: Option<u64>,
+}
+
+impl TryFrom<walletrpc::Payment> for PaymentInfo {
+    type Error = ClientError;
+
+    fn try_from(payment: walletrpc::Payment) -> Result<Self, Self::Error> {
+        Ok(PaymentInfo {
+            hash: PaymentHash(parse_bytes("hash", &payment.hash)?),
+            preimage: parse_optional_bytes("preimage", &payment.preimage)?.map(PaymentPreimage),
+            secret: parse_optional_bytes("secret", &payment.secret)?.map(PaymentSecret),
+            amount_msat: payment
+                .amount_msat
+                .map(|msat| amount_msat("amount_msat", msat))
+                .transpose()?,
+            direction: PaymentDirection::try_from(payment.direction).map_err(|_| {
+                ClientError::InvalidReply(format!("direction: {}", payment.direction))
+            })?,
+            status: PaymentStatus::try_from(payment.status)
+                .map_err(|_| ClientError::InvalidReply(format!("status: {}", payment.status)))?,
+            timestamp: payment.timestamp,
+        })
+    }
+}
+
+fn node_name(node_name: &str) -> GeneralNodeNameRequest {
+    GeneralNodeNameRequest {
+        node_name: node_name.to_string(),
+    }
+}
+
+/// Async client of walletrpc-server, replies are parsed into the bitcoin and lightning
+/// types. The connection is shared by clones and reestablished when it drops.
+#[derive(Clone)]
+pub struct WalletClient {
+    inner: RawClient,
+    max_retries: u32,
+    retry_backoff: Duration,
+}
+
+impl WalletClient {
+    /// Connects right away, failing if the server can't be reached.
+    pub async fn connect(config: ClientConfig) -> Result<Self, ClientError> {
+        let endpoint = config.endpoint()?;
+        let channel = match config.server.strip_prefix(UNIX_PREFIX) {
+            Some(path) => {
+                let path = path.to_string();
+                endpoint
+                    .connect_with_connector(tower::service_fn(move |_| {
+                        tokio::net::UnixStream::connect(path.clone())
+                    }))
+                    .await?
+            }
+            None => endpoint.connect().await?,
+        };
+        Ok(WalletClient::new(channel, config))
+    }
+
+    /// Connects on the first call instead, so the server doesn't have to be up yet.
+    pub fn connect_lazy(config: ClientConfig) -> Result<Self, ClientError> {
+        let endpoint = config.endpoint()?;
+        let channel = match config.server.strip_prefix(UNIX_PREFIX) {
+            Some(path) => {
+                let path = path.to_string();
+                endpoint.connect_with_connector_lazy(tower::service_fn(move |_| {
+                    tokio::net::UnixStream::connect(path.clone())
+                }))
+            }
+            None => endpoint.connect_lazy(),
+        };
+        Ok(WalletClient::new(channel, config))
+    }
+
+    fn new(channel: Channel, config: ClientConfig) -> Self {
+        WalletClient {
+            inner: WalletRpcServiceClient::with_interceptor(channel, Bearer(config.token)),
+            max_retries: config.max_retries,
+            retry_backoff: config.retry_backoff,
+        }
+    }
+
+    /// The generated client sharing this connection.
+    pub fn raw(&self) -> RawClient {
+        self.inner.clone()
+    }
+
+    /// Makes an idempotent call, again while the server is unavailable.
+    async fn retry<T, F, Fut>(&self, call: F) -> Result<T, ClientError>
+    where
+        F: Fn(RawClient) -> Fut,
+        Fut: Future<Output = Result<Response<T>, Status>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match call(self.inner.clone()).await {
+                Ok(response) => return Ok(response.into_inner()),
+                Err(status) if status.code() == Code::Unavailable && attempt < self.max_retries => {
+                    attempt += 1;
+                    tokio::time::sleep(self.retry_backoff * attempt).await;
+                }
+                Err(status) => return Err(status.into()),
+            }
+        }
+    }
+
+    pub async fn list_wallets(&self) -> Result<Vec<String>, ClientError> {
+        let reply = self
+            .retry(|mut client| async move { client.list_wallets(ListWalletsRequest {}).await })
+            .await?;
+        Ok(reply.wallet_names)
+    }
+
+    pub async fn get_wallet_config(
+        &self,
+        wallet_name: &str,
+    ) -> Result<GetWalletConfigReply, ClientError> {
+        self.retry(|mut client| {
+            let request = node_name(wallet_name);
+            async move { client.get_wallet_config(request).await }
+        })
+        .await
+    }
+
+    /// Decrypts the seed of the wallet on the server, needed before its node is started.
+    pub async fn unlock_wallet(
+        &self,
+        wallet_name: &str,
+        password: &str,
+    ) -> Result<(), ClientError> {
+        self.retry(|mut client| {
+            let request = UnlockWalletRequest {
+                wallet_name: wallet_name.to_string(),
+                password: password.to_string(),
+            };
+            async move { client.unlock_wallet(request).await }
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// The mnemonic of the wallet, the password is needed even when it's unlocked.
+    pub async fn export_mnemonic(
+        &self,
+        wallet_name: &str,
+        password: &str,
+    ) -> Result<SecretMnemonic, ClientError> {
+        let reply = self
+            .retry(|mut client| {
+                let request = ExportMnemonicRequest {
+                    wallet_name: wallet_name.to_string(),
+                    password: password.to_string(),
+                };
+                async move { client.export_mnemonic(request).await }
+            })
+            .await?;
+        SecretMnemonic::parse(&reply.mnemonic)
+            .map_err(|e| ClientError::InvalidReply(format!("mnemonic: {}", e)))
+    }
+
+    pub async fn change_password(
+        &self,
+        wallet_name: &str,
+        old_password: &str,
+        new_password: &str,
+    ) -> Result<(), ClientError> {
+        let request = ChangePasswordRequest {
+            wallet_name: wallet_name.to_string(),
+            old_password: old_password.to_string(),
+            new_password: new_password.to_string(),
+        };
+        self.raw().change_password(request).await?;
+        Ok(())
+    }
+
+    pub async fn start_node(&self, node: &str) -> Result<(), ClientError> {
+        self.raw().start_node(node_name(node)).await?;
+        Ok(())
+    }
+
+    pub async fn stop_node(&self, node: &str) -> Result<(), ClientError> {
+        self.raw().stop_node(node_name(node)).await?;
+        Ok(())
+    }
+
+    pub async fn restart_node(&self, node: &str) -> Result<(), ClientError> {
+        self.raw().restart_node(node_name(node)).await?;
+        Ok(())
+    }
+
+    pub async fn is_node_running(&self, node: &str) -> Result<bool, ClientError> {
+        let reply = self
+            .retry(|mut client| {
+                let request = node_name(node);
+                async move { client.is_node_running(request).await }
+            })
+            .await?;
+        Ok(reply.success)
+    }
+
+    pub async fn get_node_id(&self, node: &str) -> Result<PublicKey, ClientError> {
+        let reply = self
+            .retry(|mut client| {
+                let request = node_name(node);
+                async move { client.get_node_id(request).await }
+            })
+            .await?;
+        parse("node_id", &reply.node_id)
+    }
+
+    /// A new address each call, so it isn't retried.
+    pub async fn get_on_chain_address(
+        &self,
+        node: &str,
+    ) -> Result<Address<NetworkUnchecked>, ClientError> {
+        let reply = self
+            .raw()
+            .get_on_chain_address(node_name(node))
+            .await?
+            .into_inner();
+        parse("address", &reply.address)
+    }
+
+    pub async fn get_balances(&self, node: &str) -> Result<Balances, ClientError> {
+        let reply = self
+            .retry(|mut client| {
+                let request = node_name(node);
+                async move { client.get_balances(request).await }
+            })
+            .await?;
+        Balances::try_from(reply)
+    }
+
+    pub async fn list_channels(&self, node: &str) -> Result<Vec<ChannelInfo>, ClientError> {
+        let reply = self
+            .retry(|mut client| {
+                let request = node_name(node);
+                async move { client.list_channels(request).await }
+            })
+            .await?;
+        let mut channels: Vec<_> = reply.channels.into_iter().collect();
+        channels.sort_by_key(|(index, _)| *index);
+        channels
+            .into_iter()
+            .map(|(_, channel)| ChannelInfo::try_from(channel))
+            .collect()
+    }
+
+    pub async fn list_peers(&self, node: &str) -> Result<Vec<PeerInfo>, ClientError> {
+        let reply = self
+            .retry(|mut client| {
+                let request = node_name(node);
+                async move { client.list_peers(request).await }
+            })
+            .await?;
+        let mut peers: Vec<_> = reply.peers.into_iter().collect();
+        peers.sort_by_key(|(index, _)| *index);
+        peers
+            .into_iter()
+            .map(|(_, peer)| PeerInfo::try_from(peer))
+            .collect()
+    }
+
+    pub async fn connect_to_peer(
+        &self,
+        node: &str,
+        node_id: PublicKey,
+        net_address: &str,
+    ) -> Result<(), ClientError> {
+        let request = ConnectToPeerRequest {
+            our_node_name: node.to_string(),
+            node_id: node_id.to_string(),
+            net_address: net_address.to_string(),
+        };
+        self.retry(|mut client| {
+            let request = request.clone();
+            async move { client.connect_to_peer(request).await }
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn disconnect_peer(&self, node: &str, node_id: PublicKey) -> Result<(), ClientError> {
+        let request = DisconnectPeerRequest {
+            our_node_name: node.to_string(),
+            node_id: node_id.to_string(),
+        };
+        self.retry(|mut client| {
+            let request = request.clone();
+            async move { client.disconnect_peer(request).await }
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn open_channel(
+        &self,
+        node: &str,
+        node_id: PublicKey,
+        net_address: &str,
+        channel_amount: Amount,
+        push_to_counterparty_msat: u64,
+        announce_channel: bool,
+    ) -> Result<(), ClientError> {
+        let request = OpenChannelRequest {
+            our_node_name: node.to_string(),
+            node_id: node_id.to_string(),
+            net_address: net_address.to_string(),
+            channel_amount_sats: channel_amount.to_sat() as i64,
+            push_to_counterparty_msat: push_to_counterparty_msat as i64,
+            announce_channel,
+        };
+        self.raw().open_channel(request).await?;
+        Ok(())
+    }
+
+    pub async fn close_channel(
+        &self,
+        node: &str,
+        channel_id: &ChannelId,
+        counterparty_node_id: PublicKey,
+    ) -> Result<(), ClientError> {
+        let request = walletrpc::CloseChannelRequest {
+            our_node_name: node.to_string(),
+            channel_id: bytes_to_hex(&channel_id.0),
+            node_id: counterparty_node_id.to_string(),
+        };
+        self.raw().close_channel(request).await?;
+        Ok(())
+    }
+
+    pub async fn create_invoice(
+        &self,
+        node: &str,
+        amount_msat: u64,
+        description: &str,
+        expiry_secs: u32,
+    ) -> Result<Bolt11Invoice, ClientError> {
+        let request = CreateInvoiceRequest {
+            our_node_name: node.to_string(),
+            amount_msat: amount_msat as i64,
+            description: description.to_string(),
+            expiry_secs: expiry_secs as i32,
+        };
+        let reply = self.raw().create_invoice(request).await?.into_inner();
+        parse("invoice", &reply.invoice)
+    }
+
+    pub async fn pay_invoice(
+        &self,
+        node: &str,
+        invoice: &Bolt11Invoice,
+    ) -> Result<(), ClientError> {
+        let request = PayInvoiceRequest {
+            our_node_name: node.to_string(),
+            invoice: invoice.to_string(),
+        };
+        self.raw().pay_invoice(request).await?;
+        Ok(())
+    }
+
+    pub async fn send_to_address(
+        &self,
+        node: &str,
+        address: &Address,
+        amount: Amount,
+    ) -> Result<Txid, ClientError> {
+        let request = SendToAddressRequest {
+            our_node_name: node.to_string(),
+            address: address.to_string(),
+            amount_sats: amount.to_sat(),
+            fee_rate_sat_per_vb: None,
+        };
+        let reply = self.raw().send_to_address(request).await?.into_inner();
+        parse("txid", &reply.txid)
+    }
+
+    pub async fn sweep_all(&self, node: &str, address: &Address) -> Result<Txid, ClientError> {
+        let request = SweepAllRequest {
+            our_node_name: node.to_string(),
+            address: address.to_string(),
+        };
+        let reply = self.raw().sweep_all(request).await?.into_inner();
+        parse("txid", &reply.txid)
+    }
+
+    /// Every payment matching the filters, oldest first, see `ListPayments` in the proto
+    /// file for the order and the time range.
+    pub async fn list_payments(
+        &self,
+        node: &str,
+        direction: Option<PaymentDirection>,
+        status: Option<PaymentStatus>,
+        since: Option<u64>,
+        until: Option<u64>,
+    ) -> Result<Vec<PaymentInfo>, ClientError> {
+        let request = ListPaymentsRequest {
+            our_node_name: node.to_string(),
+            direction: direction.map(|d| d.into()),
+            status: status.map(|s| s.into()),
+            offset: 0,
+            limit: 0,
+            since,
+            until,
+        };
+        let reply = self
+            .retry(|mut client| {
+                let request = request.clone();
+                async move { client.list_payments(request).await }
+            })
+            .await?;
+        reply
+            .payments
+            .into_iter()
+            .map(PaymentInfo::try_from)
+            .collect()
+    }
+
+    pub async fn get_payment(
+        &self,
+        node: &str,
+        payment_hash: &PaymentHash,
+    ) -> Result<PaymentInfo, ClientError> {
+        let request = GetPaymentRequest {
+            our_node_name: node.to_string(),
+            payment_hash: bytes_to_hex(&payment_hash.0),
+        };
+        let reply = self
+            .retry(|mut client| {
+                let request = request.clone();
+                async move { client.get_payment(request).await }
+            })
+            .await?;
+        PaymentInfo::try_from(reply)
+    }
+
+    /// Streams the events of a started node, see `SubscribeEvents` in the proto file.
+    pub async fn subscribe_events(&self, node: &str) -> Result<Streaming<NodeEvent>, ClientError> {
+        Ok(self
+            .raw()
+            .subscribe_events(node_name(node))
+            .await?
+            .into_inner())
+    }
+}
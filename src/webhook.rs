@@ -0,0 +1,328 @@
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+
+use ldk_node::bitcoin::hashes::hmac::{Hmac, HmacEngine};
+use ldk_node::bitcoin::hashes::{sha256, Hash, HashEngine};
+use serde::Serialize;
+
+use crate::wallet::Webhook;
+use crate::walletrpc::NodeEvent;
+
+// home_dir/.bits-wallet/wallets/wallet_name/ldk-data/webhooks.dead-letter.jsonl
+
+const DEAD_LETTER_FILE: &str = "webhooks.dead-letter.jsonl";
+/// Header carrying `sha256=<hex>`, the HMAC-SHA256 of the body keyed with the webhook secret.
+pub const SIGNATURE_HEADER: &str = "X-Walletrpc-Signature";
+const ATTEMPTS: u32 = 5;
+/// Delay before the first retry, doubled for every following one.
+const BACKOFF: Duration = Duration::from_millis(500);
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// How long stopping a node waits for the delivery in flight to return.
+const STOP_GRACE: Duration = Duration::from_secs(2);
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    wallet_name: &'a str,
+    #[serde(flatten)]
+    event: &'a NodeEvent,
+}
+
+#[derive(Serialize)]
+struct DeadLetter<'a> {
+    url: &'a str,
+    attempts: u32,
+    error: &'a str,
+    payload: &'a str,
+}
+
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut engine = HmacEngine::<sha256::Hash>::new(secret.as_bytes());
+    engine.input(body);
+    format!("sha256={}", Hmac::<sha256::Hash>::from_engine(engine))
+}
+
+/// POSTs the signed body once, only a 2xx response counts as delivered.
+fn post(client: &reqwest::blocking::Client, webhook: &Webhook, body: &str) -> Result<(), String> {
+    let response = client
+        .post(&webhook.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, sign(&webhook.secret, body.as_bytes()))
+        .body(body.to_string())
+        .send()
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("webhook responded with {}", response.status()));
+    }
+    Ok(())
+}
+
+/// POSTs the body until it is delivered or every attempt failed, with exponential backoff
+/// between attempts. Before each retry `wait` is given the attempts made so far and the
+/// backoff, and the retries are given up on when it returns false. Returns the attempts
+/// made along with the result of the last one.
+fn post_with_retries(
+    client: &reqwest::blocking::Client,
+    webhook: &Webhook,
+    body: &str,
+    mut wait: impl FnMut(u32, Duration) -> bool,
+) -> (u32, Result<(), String>) {
+    let mut backoff = BACKOFF;
+    let mut result = post(client, webhook, body);
+    for attempts in 1..ATTEMPTS {
+        if result.is_ok() || !wait(attempts, backoff) {
+            return (attempts, result);
+        }
+        backoff *= 2;
+        result = post(client, webhook, body);
+    }
+    (ATTEMPTS, result)
+}
+
+fn client() -> Result<reqwest::blocking::Client, String> {
+    reqwest::blocking::Client::builder()
+        .timeout(TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+fn payload(wallet_name: &str, event: &NodeEvent) -> Result<String, String> {
+    serde_json::to_string(&Payload { wallet_name, event }).map_err(|e| e.to_string())
+}
+
+/// Delivers the event to the webhook, retrying with exponential backoff. Once every
+/// attempt failed, the payload is appended to the dead-letter log next to the journal.
+pub fn deliver(
+    ldk_data_dir: &str,
+    wallet_name: &str,
+    webhook: &Webhook,
+    event: &NodeEvent,
+) -> Result<(), String> {
+    let client = client()?;
+    let body = payload(wallet_name, event)?;
+    let (attempts, result) = post_with_retries(&client, webhook, &body, |_, backoff| {
+        thread::sleep(backoff);
+        true
+    });
+    if let Err(error) = &result {
+        log_dead_letter(ldk_data_dir, &webhook.url, attempts, error, &body);
+    }
+    result
+}
+
+fn log_dead_letter(ldk_data_dir: &str, url: &str, attempts: u32, error: &str, payload: &str) {
+    let letter = DeadLetter {
+        url,
+        attempts,
+        error,
+        payload,
+    };
+    if let Err(e) = dead_letter(ldk_data_dir, &letter) {
+        eprintln!("Unable to dead-letter webhook payload: {}", e);
+    }
+}
+
+fn dead_letter(ldk_data_dir: &str, letter: &DeadLetter) -> std::io::Result<()> {
+    let mut line = serde_json::to_string(letter)?;
+    line.push('\n');
+    let path = PathBuf::from(ldk_data_dir).join(DEAD_LETTER_FILE);
+    let mut file = OpenOptions::new().append(true).create(true).open(path)?;
+    file.write_all(line.as_bytes())?;
+    file.sync_data()
+}
+
+/// A delivery without an event and with `seq` 0, to check that a webhook is reachable.
+pub fn ping() -> NodeEvent {
+    NodeEvent {
+        timestamp: SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+        ..Default::default()
+    }
+}
+
+#[derive(Clone)]
+struct Delivery {
+    webhook: Webhook,
+    event: NodeEvent,
+    /// Failed attempts so far.
+    attempts: u32,
+}
+
+#[derive(Default)]
+struct Queue {
+    pending: VecDeque<Delivery>,
+    in_flight: Option<Delivery>,
+    stopping: bool,
+}
+
+/// The queue of a node's deliveries, shared by its delivery thread and the node stopping it.
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Waits for the next delivery and marks it in flight, `None` once stopping.
+    fn next(&self) -> Option<Delivery> {
+        let mut queue = self.lock();
+        while queue.pending.is_empty() && !queue.stopping {
+            queue = self
+                .changed
+                .wait(queue)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        if queue.stopping {
+            return None;
+        }
+        queue.in_flight = queue.pending.pop_front();
+        queue.in_flight.clone()
+    }
+
+    /// Records the failed attempts of the delivery in flight and waits out the backoff,
+    /// false if the deliveries were stopped meanwhile.
+    fn backoff(&self, attempts: u32, backoff: Duration) -> bool {
+        let mut queue = self.lock();
+        if let Some(delivery) = &mut queue.in_flight {
+            delivery.attempts = attempts;
+        }
+        let (queue, _) = self
+            .changed
+            .wait_timeout_while(queue, backoff, |queue| !queue.stopping)
+            .unwrap_or_else(PoisonError::into_inner);
+        !queue.stopping
+    }
+}
+
+/// The webhook deliveries of one node, made in order by their own thread so that a slow
+/// webhook holds back deliveries but never the node.
+pub struct Deliveries {
+    ldk_data_dir: String,
+    wallet_name: String,
+    webhooks: Vec<Webhook>,
+    shared: Arc<Shared>,
+    thread: JoinHandle<()>,
+}
+
+impl Deliveries {
+    /// Queues the event for every webhook.
+    pub fn send(&self, event: &NodeEvent) {
+        let mut queue = self.shared.lock();
+        for webhook in &self.webhooks {
+            queue.pending.push_back(Delivery {
+                webhook: webhook.clone(),
+                event: event.clone(),
+                attempts: 0,
+            });
+        }
+        self.shared.changed.notify_all();
+    }
+
+    /// Stops delivering without waiting on retries. Queued deliveries are dead-lettered
+    /// right away, and the one in flight once its attempt fails or, if it is still under
+    /// way after `STOP_GRACE`, then. The events stay in the journal either way.
+    pub fn stop(self) {
+        let pending = {
+            let mut queue = self.shared.lock();
+            queue.stopping = true;
+            std::mem::take(&mut queue.pending)
+        };
+        self.shared.changed.notify_all();
+        for delivery in pending {
+            self.dead_letter(&delivery, "the node stopped before delivery");
+        }
+        let deadline = Instant::now() + STOP_GRACE;
+        while !self.thread.is_finished() && Instant::now() < deadline {
+            thread::sleep(STOP_POLL_INTERVAL);
+        }
+        if self.thread.is_finished() {
+            if self.thread.join().is_err() {
+                eprintln!("The webhook thread of {} panicked", self.wallet_name);
+            }
+            return;
+        }
+        // left to end on its own once its attempt returns
+        let in_flight = self.shared.lock().in_flight.take();
+        if let Some(delivery) = in_flight {
+            self.dead_letter(&delivery, "still being delivered when the node stopped");
+        }
+    }
+
+    fn dead_letter(&self, delivery: &Delivery, error: &str) {
+        dead_letter_delivery(&self.ldk_data_dir, &self.wallet_name, delivery, error);
+    }
+}
+
+fn dead_letter_delivery(ldk_data_dir: &str, wallet_name: &str, delivery: &Delivery, error: &str) {
+    match payload(wallet_name, &delivery.event) {
+        Ok(body) => log_dead_letter(
+            ldk_data_dir,
+            &delivery.webhook.url,
+            delivery.attempts,
+            error,
+            &body,
+        ),
+        Err(e) => eprintln!("Unable to dead-letter webhook payload: {}", e),
+    }
+}
+
+fn run(ldk_data_dir: &str, wallet_name: &str, shared: &Shared) {
+    let client = client();
+    while let Some(delivery) = shared.next() {
+        let body = payload(wallet_name, &delivery.event);
+        let (attempts, result) = match (&client, &body) {
+            (Ok(client), Ok(body)) => {
+                post_with_retries(client, &delivery.webhook, body, |attempts, backoff| {
+                    shared.backoff(attempts, backoff)
+                })
+            }
+            (Err(e), _) | (_, Err(e)) => (0, Err(e.clone())),
+        };
+        // unless the node stopping took it over already
+        let in_flight = shared.lock().in_flight.take();
+        if let (Some(mut delivery), Err(error)) = (in_flight, result) {
+            eprintln!(
+                "Unable to deliver event {} to {}: {}",
+                delivery.event.seq, delivery.webhook.url, error
+            );
+            delivery.attempts = attempts;
+            dead_letter_delivery(ldk_data_dir, wallet_name, &delivery, &error);
+        }
+    }
+}
+
+/// Spawns the thread delivering the events of one node to its webhooks. Returns `None`
+/// when the wallet has no webhooks.
+pub fn spawn(
+    ldk_data_dir: String,
+    wallet_name: String,
+    webhooks: Vec<Webhook>,
+) -> Option<Deliveries> {
+    if webhooks.is_empty() {
+        return None;
+    }
+    let shared = Arc::new(Shared::default());
+    let thread = {
+        let (ldk_data_dir, wallet_name, shared) =
+            (ldk_data_dir.clone(), wallet_name.clone(), shared.clone());
+        thread::spawn(move || run(&ldk_data_dir, &wallet_name, &shared))
+    };
+    Some(Deliveries {
+        ldk_data_dir,
+        wallet_name,
+        webhooks,
+        shared,
+        thread,
+    })
+}
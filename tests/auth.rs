@@ -0,0 +1,166 @@
+mod common;
+
+use std::time::Duration;
+
+use common::walletrpc::*;
+use common::{assert_code, node_name, Server};
+use tonic::Code;
+
+fn new_wallet(wallet_name: &str) -> NewWalletRequest {
+    NewWalletRequest {
+        network: Network::Regtest as i32,
+        wallet_name: wallet_name.to_string(),
+        listening_address: "127.0.0.1:9735".to_string(),
+        esplora_address: "http://127.0.0.1:3002".to_string(),
+        allow_mainnet: false,
+        password: "password".to_string(),
+        passphrase: String::new(),
+    }
+}
+
+fn bake(permission: Permission, wallet_names: &[&str], expires_in_secs: u64) -> BakeTokenRequest {
+    BakeTokenRequest {
+        permission: permission as i32,
+        wallet_names: wallet_names.iter().map(|w| w.to_string()).collect(),
+        expires_in_secs,
+    }
+}
+
+#[tokio::test]
+async fn auth() {
+    let server = Server::start("auth");
+    let mut admin = server.connect().await;
+    admin.new_wallet(new_wallet("alice")).await.unwrap();
+    admin.new_wallet(new_wallet("bob")).await.unwrap();
+
+    // Calls without a valid token are refused.
+    let mut anonymous = server.connect_as(None).await;
+    assert_code(
+        anonymous.list_wallets(ListWalletsRequest {}).await,
+        Code::Unauthenticated,
+    );
+    let mut forged = server.connect_as(Some("not-a-token")).await;
+    assert_code(
+        forged.list_wallets(ListWalletsRequest {}).await,
+        Code::Unauthenticated,
+    );
+
+    // A read-only token reads but neither receives nor spends.
+    let read_only = admin
+        .bake_token(bake(Permission::ReadOnly, &[], 0))
+        .await
+        .unwrap()
+        .into_inner();
+    let mut client = server.connect_as(Some(&read_only.token)).await;
+    client.get_wallet_config(node_name("alice")).await.unwrap();
+    let invoice = CreateInvoiceRequest {
+        our_node_name: "alice".to_string(),
+        amount_msat: 1000,
+        description: "auth".to_string(),
+        expiry_secs: 60,
+    };
+    assert_code(
+        client.create_invoice(invoice.clone()).await,
+        Code::PermissionDenied,
+    );
+    assert_code(
+        client
+            .pay_invoice(PayInvoiceRequest {
+                our_node_name: "alice".to_string(),
+                invoice: "lnbcrt1".to_string(),
+            })
+            .await,
+        Code::PermissionDenied,
+    );
+    assert_code(
+        client.bake_token(bake(Permission::ReadOnly, &[], 0)).await,
+        Code::PermissionDenied,
+    );
+
+    // An invoice token gets past authorization, the node just isn't running.
+    let invoice_token = admin
+        .bake_token(bake(Permission::Invoice, &["alice"], 0))
+        .await
+        .unwrap()
+        .into_inner();
+    let mut client = server.connect_as(Some(&invoice_token.token)).await;
+    let status = client.create_invoice(invoice).await.unwrap_err();
+    assert_ne!(status.code(), Code::PermissionDenied);
+    assert_ne!(status.code(), Code::Unauthenticated);
+
+    // A token limited to a wallet only sees and touches that wallet.
+    let wallets = client
+        .list_wallets(ListWalletsRequest {})
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(wallets.wallet_names, vec!["alice".to_string()]);
+    assert_code(
+        client.get_wallet_config(node_name("bob")).await,
+        Code::PermissionDenied,
+    );
+    let wallets = admin
+        .list_wallets(ListWalletsRequest {})
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(wallets.wallet_names.len(), 2);
+
+    // Scoped admin tokens can't manage tokens.
+    let scoped_admin = admin
+        .bake_token(bake(Permission::Admin, &["alice"], 0))
+        .await
+        .unwrap()
+        .into_inner();
+    let mut client = server.connect_as(Some(&scoped_admin.token)).await;
+    assert_code(
+        client.list_tokens(ListTokensRequest {}).await,
+        Code::PermissionDenied,
+    );
+
+    // Expired tokens are refused.
+    let expiring = admin
+        .bake_token(bake(Permission::ReadOnly, &[], 1))
+        .await
+        .unwrap()
+        .into_inner();
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let mut client = server.connect_as(Some(&expiring.token)).await;
+    assert_code(
+        client.list_wallets(ListWalletsRequest {}).await,
+        Code::Unauthenticated,
+    );
+
+    // Revoked tokens are refused right away, only their hash is kept.
+    let tokens = admin
+        .list_tokens(ListTokensRequest {})
+        .await
+        .unwrap()
+        .into_inner()
+        .tokens;
+    assert_eq!(tokens.len(), 5);
+    let info = tokens.iter().find(|t| t.id == invoice_token.id).unwrap();
+    assert_eq!(info.permission, Permission::Invoice as i32);
+    assert_eq!(info.wallet_names, vec!["alice".to_string()]);
+    admin
+        .revoke_token(RevokeTokenRequest {
+            id: invoice_token.id.clone(),
+        })
+        .await
+        .unwrap();
+    assert_code(
+        admin
+            .revoke_token(RevokeTokenRequest {
+                id: invoice_token.id,
+            })
+            .await,
+        Code::NotFound,
+    );
+    let mut client = server.connect_as(Some(&invoice_token.token)).await;
+    assert_code(
+        client.list_wallets(ListWalletsRequest {}).await,
+        Code::Unauthenticated,
+    );
+    let store = std::fs::read_to_string(server.home_dir.join(".bits-wallet/tokens.json")).unwrap();
+    assert!(!store.contains(&read_only.token));
+}
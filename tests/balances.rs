@@ -0,0 +1,50 @@
+mod common;
+
+use common::walletrpc::*;
+use common::{fake_esplora, node_name, Server};
+
+#[tokio::test]
+async fn balances() {
+    let server = Server::start("balances");
+    let mut client = server.connect().await;
+
+    client
+        .new_wallet(NewWalletRequest {
+            network: Network::Regtest as i32,
+            wallet_name: "balances".to_string(),
+            listening_address: "127.0.0.1:9741".to_string(),
+            esplora_address: fake_esplora(),
+            allow_mainnet: false,
+            password: "password".to_string(),
+            passphrase: String::new(),
+        })
+        .await
+        .unwrap();
+    client.start_node(node_name("balances")).await.unwrap();
+
+    // A new wallet has nothing on-chain and no channels.
+    let balances = client
+        .get_balances(node_name("balances"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(
+        balances,
+        GetBalancesReply {
+            total_onchain_balance_sats: 0,
+            spendable_onchain_balance_sats: 0,
+            total_lightning_balance_msat: 0,
+            outbound_capacity_msat: 0,
+            inbound_capacity_msat: 0,
+            channels: Vec::new(),
+        }
+    );
+    let onchain = client
+        .get_on_chain_balance(node_name("balances"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(onchain.balance as u64, balances.total_onchain_balance_sats);
+
+    client.stop_node(node_name("balances")).await.unwrap();
+}
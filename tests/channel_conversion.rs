@@ -0,0 +1,55 @@
+use ldk_node::lightning::ln::ChannelId;
+use walletrpc_tonic::lightning::{parse_public_key, ChanDetails, LightningError};
+use walletrpc_tonic::walletrpc::Channel;
+
+const NODE_ID: &str = "02eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619";
+
+#[test]
+fn channel_conversion() {
+    let details = ChanDetails {
+        channel_id: ChannelId([0xab; 32]),
+        user_channel_id: u128::MAX - 1,
+        counterparty_node_id: parse_public_key(NODE_ID).unwrap(),
+        channel_value_sats: 100_000,
+        balance_msat: 40_000_000,
+        outbound_capacity_msat: 30_000_000,
+        inbound_capacity_msat: 60_000_000,
+        is_outbound: true,
+        is_channel_ready: true,
+        is_usable: false,
+        is_public: true,
+    };
+
+    // Ids are sent as lowercase hex and read back unchanged.
+    let channel = Channel::from(details.clone());
+    assert_eq!(channel.channel_id, "ab".repeat(32));
+    assert_eq!(channel.user_channel_id, format!("{}fe", "f".repeat(30)));
+    assert_eq!(channel.counterparty_node_id, NODE_ID);
+    assert_eq!(ChanDetails::try_from(channel.clone()).unwrap(), details);
+
+    // Malformed hex is rejected rather than truncated or zero-padded.
+    let malformed = Channel {
+        channel_id: "zz".repeat(32),
+        ..channel.clone()
+    };
+    assert!(matches!(
+        ChanDetails::try_from(malformed),
+        Err(LightningError::InvalidChannelId(_))
+    ));
+    let short = Channel {
+        channel_id: "ab".repeat(31),
+        ..channel.clone()
+    };
+    assert!(matches!(
+        ChanDetails::try_from(short),
+        Err(LightningError::InvalidChannelId(_))
+    ));
+    let malformed = Channel {
+        user_channel_id: "xyz".to_string(),
+        ..channel
+    };
+    assert!(matches!(
+        ChanDetails::try_from(malformed),
+        Err(LightningError::InvalidChannelId(_))
+    ));
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use std::process::{Command, Output};
+
+use common::Server;
+
+fn run(server: &Server, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_walletrpc-cli"))
+        .env("HOME", &server.home_dir)
+        .args(args)
+        .output()
+        .expect("failed to run walletrpc-cli")
+}
+
+fn stdout(output: Output) -> String {
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[tokio::test]
+async fn cli() {
+    let server = Server::start("cli");
+    server.connect().await;
+
+    // The generated certificate and the admin token are found in the data dir.
+    let mnemonic = stdout(run(
+        &server,
+        &[
+            "newwallet",
+            "carol",
+            "--network",
+            "regtest",
+            "--listening-address",
+            "127.0.0.1:9735",
+            "--esplora-address",
+            "http://127.0.0.1:3002",
+            "--password",
+            "password",
+        ],
+    ));
+    assert_eq!(
+        mnemonic
+            .trim()
+            .trim_start_matches("mnemonic:")
+            .split_whitespace()
+            .count(),
+        12
+    );
+
+    let table = stdout(run(&server, &["listwallets"]));
+    assert_eq!(table, "WALLET_NAME\ncarol\n");
+
+    let config = stdout(run(&server, &["getwalletconfig", "carol", "--json"]));
+    let config: serde_json::Value = serde_json::from_str(&config).unwrap();
+    assert_eq!(config["network"], "Regtest");
+    assert_eq!(config["esplora_address"], "http://127.0.0.1:3002");
+
+    let token = stdout(run(&server, &["baketoken", "readonly", "--json"]));
+    let token: serde_json::Value = serde_json::from_str(&token).unwrap();
+    let tokens = stdout(run(&server, &["listtokens", "--json"]));
+    let tokens: serde_json::Value = serde_json::from_str(&tokens).unwrap();
+    assert_eq!(tokens["tokens"][1]["permission"], "ReadOnly");
+
+    // Errors go to stderr with their code and kind, and fail the command.
+    let token = token["token"].as_str().unwrap();
+    let output = run(&server, &["--token", token, "deletewallet", "carol"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.starts_with("PermissionDenied PermissionDenied:"),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,223 @@
+#![allow(dead_code)]
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus};
+use std::time::Duration;
+
+use prost::Message;
+use tonic::service::interceptor::InterceptedService;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint};
+use tonic::Code;
+use walletrpc::wallet_rpc_service_client::WalletRpcServiceClient;
+use walletrpc::{ErrorDetail, GeneralNodeNameRequest};
+use walletrpc_tonic::wallet_client::Bearer;
+
+pub mod walletrpc {
+    tonic::include_proto!("walletrpc");
+}
+
+const SERVER_ADDRESS: &str = "https://[::1]:50051";
+
+pub type Client = WalletRpcServiceClient<InterceptedService<Channel, Bearer>>;
+
+/// A walletrpc-server process with its own home directory, killed and cleaned up on drop.
+pub struct Server {
+    child: Child,
+    pub home_dir: PathBuf,
+    /// Where the server keeps its files, `~/.bits-wallet` unless configured otherwise.
+    pub base_dir: PathBuf,
+}
+
+impl Server {
+    pub fn start(name: &str) -> Self {
+        Server::start_with_env(name, &[])
+    }
+
+    pub fn start_with_env(name: &str, envs: &[(&str, &str)]) -> Self {
+        let home_dir =
+            std::env::temp_dir().join(format!("walletrpc-test-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&home_dir).unwrap();
+        let child = Command::new(env!("CARGO_BIN_EXE_walletrpc-server"))
+            .env("HOME", &home_dir)
+            .envs(envs.iter().copied())
+            .spawn()
+            .expect("failed to spawn walletrpc-server");
+        let base_dir = match envs.iter().find(|(k, _)| *k == "WALLETRPC_DATA_DIR") {
+            Some((_, dir)) => PathBuf::from(dir),
+            None => home_dir.join(".bits-wallet"),
+        };
+        Server {
+            child,
+            home_dir,
+            base_dir,
+        }
+    }
+
+    /// The certificate the server generates on first run.
+    pub fn tls_cert(&self) -> PathBuf {
+        self.base_dir.join("tls.cert")
+    }
+
+    /// The admin token the server bakes on first run, before it starts listening.
+    pub fn admin_token(&self) -> String {
+        let path = self.base_dir.join("admin.token");
+        for _ in 0..50 {
+            if let Ok(token) = std::fs::read_to_string(&path) {
+                return token;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        panic!("walletrpc-server did not bake {:?}", path);
+    }
+
+    /// Connects with the admin token, trusting the generated certificate.
+    pub async fn connect(&self) -> Client {
+        self.connect_as(Some(&self.admin_token())).await
+    }
+
+    /// Connects with the given token, or none, trusting the generated certificate.
+    pub async fn connect_as(&self, token: Option<&str>) -> Client {
+        let cert = self.wait_for_cert().await;
+        let tls = ClientTlsConfig::new()
+            .ca_certificate(Certificate::from_pem(cert))
+            .domain_name("localhost");
+        connect_with(tls, token).await
+    }
+
+    /// Connects with the admin token to the Unix socket of the server, without TLS.
+    pub async fn connect_unix(&self) -> Client {
+        let token = self.admin_token();
+        let socket = self.base_dir.join("walletrpc.sock");
+        // the uri is ignored by the connector
+        let endpoint = Endpoint::from_static("http://[::1]:50051");
+        for _ in 0..50 {
+            let socket = socket.clone();
+            let connector =
+                tower::service_fn(move |_| tokio::net::UnixStream::connect(socket.clone()));
+            if let Ok(channel) = endpoint.connect_with_connector(connector).await {
+                let bearer = Bearer::new(Some(token));
+                return WalletRpcServiceClient::with_interceptor(channel, bearer);
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("walletrpc-server did not listen on {:?}", socket);
+    }
+
+    async fn wait_for_cert(&self) -> Vec<u8> {
+        for _ in 0..50 {
+            if let Ok(cert) = std::fs::read(self.tls_cert()) {
+                return cert;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("walletrpc-server did not generate {:?}", self.tls_cert());
+    }
+
+    pub fn is_alive(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    /// Sends SIGTERM and waits up to `timeout` for the server to exit.
+    pub async fn terminate(&mut self, timeout: Duration) -> Option<ExitStatus> {
+        let status = Command::new("kill")
+            .args(["-TERM", &self.child.id().to_string()])
+            .status()
+            .expect("failed to run kill");
+        assert!(status.success());
+        for _ in 0..timeout.as_millis() / 100 {
+            if let Ok(Some(status)) = self.child.try_wait() {
+                return Some(status);
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        None
+    }
+
+    pub fn wallet_file(&self, wallet_name: &str, file: &str) -> PathBuf {
+        self.base_dir.join("wallets").join(wallet_name).join(file)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.home_dir);
+        let _ = std::fs::remove_dir_all(&self.base_dir);
+    }
+}
+
+pub async fn connect_with(tls: ClientTlsConfig, token: Option<&str>) -> Client {
+    let endpoint = Channel::from_static(SERVER_ADDRESS)
+        .tls_config(tls)
+        .unwrap();
+    for _ in 0..50 {
+        if let Ok(channel) = endpoint.connect().await {
+            let bearer = Bearer::new(token.map(|t| t.to_string()));
+            return WalletRpcServiceClient::with_interceptor(channel, bearer);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("walletrpc-server did not come up on {}", SERVER_ADDRESS);
+}
+
+pub fn node_name(name: &str) -> GeneralNodeNameRequest {
+    GeneralNodeNameRequest {
+        node_name: name.to_string(),
+    }
+}
+
+/// Asserts that the call failed with `code` and carries an `ErrorDetail`.
+pub fn assert_code<T: std::fmt::Debug>(
+    result: Result<tonic::Response<T>, tonic::Status>,
+    code: Code,
+) {
+    match result {
+        Ok(r) => panic!("expected {:?}, got {:?}", code, r),
+        Err(status) => {
+            assert_eq!(status.code(), code, "{:?}", status);
+            let detail = ErrorDetail::decode(status.details()).expect("missing ErrorDetail");
+            assert!(!detail.kind.is_empty());
+        }
+    }
+}
+
+/// Serves just enough of the esplora API for a node to start, fee estimates. Chain sync
+/// requests get a 404, which the node logs and retries.
+pub fn fake_esplora() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            let _ = reader.read_line(&mut request_line);
+            loop {
+                let mut line = String::new();
+                match reader.read_line(&mut line) {
+                    Ok(n) if n > 0 && !line.trim_end().is_empty() => {}
+                    _ => break,
+                }
+            }
+            let (status, body) = if request_line.starts_with("GET /fee-estimates ") {
+                (
+                    "200 OK",
+                    r#"{"1": 20.0, "6": 10.0, "12": 5.0, "144": 2.0, "1008": 1.0}"#,
+                )
+            } else {
+                ("404 Not Found", "")
+            };
+            let response = format!(
+                "HTTP/1.1 {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    url
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use common::walletrpc::*;
+use common::{assert_code, node_name, Server};
+use prost::Message;
+use tonic::Code;
+
+#[tokio::test]
+async fn event_journal_replay() {
+    let server = Server::start("journal");
+    let mut client = server.connect().await;
+
+    client
+        .new_wallet(NewWalletRequest {
+            network: Network::Regtest as i32,
+            wallet_name: "journal".to_string(),
+            listening_address: "127.0.0.1:9735".to_string(),
+            esplora_address: "http://127.0.0.1:3002".to_string(),
+            allow_mainnet: false,
+            password: "password".to_string(),
+            passphrase: String::new(),
+        })
+        .await
+        .unwrap();
+    let replay_events = |node_name: &str, after_seq, limit| ReplayEventsRequest {
+        node_name: node_name.to_string(),
+        after_seq,
+        limit,
+    };
+    let reply = client
+        .replay_events(replay_events("journal", 0, 0))
+        .await
+        .unwrap();
+    assert!(reply.into_inner().events.is_empty());
+    assert_code(
+        client
+            .replay_events(replay_events("no-such-wallet", 0, 0))
+            .await,
+        Code::NotFound,
+    );
+
+    // A journal whose first four events were pruned, followed by a record cut short.
+    let event = |seq, payment_hash: &str| NodeEvent {
+        event: Some(node_event::Event::PaymentSuccessful(
+            PaymentSuccessfulEvent {
+                payment_hash: payment_hash.to_string(),
+            },
+        )),
+        seq,
+        timestamp: 1_700_000_000 + seq,
+    };
+    let mut journal = Vec::new();
+    for seq in 5..=7 {
+        journal.extend(event(seq, &format!("{:064x}", seq)).encode_length_delimited_to_vec());
+    }
+    let truncated = event(8, "").encode_length_delimited_to_vec();
+    journal.extend(&truncated[..truncated.len() - 1]);
+    std::fs::write(
+        server.wallet_file("journal", "ldk-data/events.journal"),
+        journal,
+    )
+    .unwrap();
+
+    let seqs = |events: Vec<NodeEvent>| events.iter().map(|e| e.seq).collect::<Vec<_>>();
+    for (after_seq, limit, expected) in [
+        (0, 0, vec![5, 6, 7]),
+        (4, 0, vec![5, 6, 7]),
+        (5, 0, vec![6, 7]),
+        (5, 1, vec![6]),
+        (7, 0, vec![]),
+        // past the newest event, without overflowing
+        (u64::MAX, 0, vec![]),
+    ] {
+        let reply = client
+            .replay_events(replay_events("journal", after_seq, limit))
+            .await
+            .unwrap();
+        assert_eq!(seqs(reply.into_inner().events), expected);
+    }
+    let reply = client
+        .replay_events(replay_events("journal", 6, 0))
+        .await
+        .unwrap();
+    assert_eq!(
+        reply.into_inner().events,
+        vec![event(7, &format!("{:064x}", 7))]
+    );
+    assert_code(
+        client.replay_events(replay_events("journal", 2, 0)).await,
+        Code::OutOfRange,
+    );
+
+    // Starting the node compacts the journal, even when it fails for lack of an esplora
+    // server. Events past the 30 day retention are dropped, except for the newest one.
+    assert_code(
+        client.start_node(node_name("journal")).await,
+        Code::Unavailable,
+    );
+    let reply = client
+        .replay_events(replay_events("journal", 0, 0))
+        .await
+        .unwrap();
+    assert_eq!(seqs(reply.into_inner().events), vec![7]);
+}
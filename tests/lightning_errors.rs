@@ -0,0 +1,40 @@
+use ldk_node::NodeError;
+use prost::Message;
+use tonic::{Code, Status};
+use walletrpc_tonic::lightning::LightningError;
+use walletrpc_tonic::wallet::WalletError;
+use walletrpc_tonic::walletrpc::ErrorDetail;
+
+#[test]
+fn lightning_errors_map_to_statuses() {
+    let cases = [
+        (
+            LightningError::NodeNotFound("alice".to_string()),
+            Code::NotFound,
+            "NodeNotFound",
+        ),
+        (
+            LightningError::InvalidPublicKey("02zz".to_string()),
+            Code::InvalidArgument,
+            "InvalidPublicKey",
+        ),
+        (
+            LightningError::Wallet(WalletError::InvalidConfig("no network".to_string())),
+            Code::FailedPrecondition,
+            "InvalidConfig",
+        ),
+        (
+            LightningError::Node(NodeError::AlreadyRunning),
+            Code::FailedPrecondition,
+            "AlreadyRunning",
+        ),
+    ];
+    for (error, code, kind) in cases {
+        let message = error.to_string();
+        let status = Status::from(error);
+        assert_eq!(status.code(), code, "{:?}", status);
+        assert_eq!(status.message(), message);
+        let detail = ErrorDetail::decode(status.details()).expect("missing ErrorDetail");
+        assert_eq!(detail.kind, kind);
+    }
+}
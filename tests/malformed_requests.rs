@@ -0,0 +1,232 @@
+mod common;
+
+use common::walletrpc::*;
+use common::{assert_code, node_name, Server};
+use tonic::Code;
+
+#[tokio::test]
+async fn malformed_requests_do_not_crash_the_server() {
+    let mut server = Server::start("malformed");
+    let mut client = server.connect().await;
+
+    let missing = "no-such-wallet";
+    assert_code(client.start_node(node_name(missing)).await, Code::NotFound);
+    assert_code(client.get_node_id(node_name(missing)).await, Code::NotFound);
+    assert_code(client.stop_node(node_name(missing)).await, Code::NotFound);
+    assert_code(
+        client.restart_node(node_name(missing)).await,
+        Code::NotFound,
+    );
+    assert_code(
+        client.get_on_chain_balance(node_name(missing)).await,
+        Code::NotFound,
+    );
+    assert_code(
+        client.get_on_chain_address(node_name(missing)).await,
+        Code::NotFound,
+    );
+    assert_code(
+        client.get_balances(node_name(missing)).await,
+        Code::NotFound,
+    );
+    assert_code(
+        client.subscribe_events(node_name(missing)).await,
+        Code::NotFound,
+    );
+    assert_code(
+        client.list_channels(node_name(missing)).await,
+        Code::NotFound,
+    );
+    assert_code(client.list_peers(node_name(missing)).await, Code::NotFound);
+    assert_code(
+        client
+            .create_invoice(CreateInvoiceRequest {
+                our_node_name: missing.to_string(),
+                amount_msat: 1000,
+                description: "test".to_string(),
+                expiry_secs: 3600,
+            })
+            .await,
+        Code::NotFound,
+    );
+    assert_code(
+        client
+            .pay_invoice(PayInvoiceRequest {
+                our_node_name: missing.to_string(),
+                invoice: "not an invoice".to_string(),
+            })
+            .await,
+        Code::NotFound,
+    );
+    // Channel ids are hex encoded, 32 bytes as returned by ListChannels.
+    for (channel_id, code) in [
+        ("too short", Code::InvalidArgument),
+        (&"ab".repeat(31), Code::InvalidArgument),
+        (&"zz".repeat(32), Code::InvalidArgument),
+        (&"ab".repeat(32), Code::NotFound),
+    ] {
+        assert_code(
+            client
+                .close_channel(CloseChannelRequest {
+                    our_node_name: missing.to_string(),
+                    channel_id: channel_id.to_string(),
+                    node_id: "".to_string(),
+                })
+                .await,
+            code,
+        );
+    }
+    // Payment hashes are hex encoded, 32 bytes as returned by ListPayments.
+    for (payment_hash, code) in [
+        ("too short", Code::InvalidArgument),
+        (&"zz".repeat(32), Code::InvalidArgument),
+        (&"ab".repeat(32), Code::NotFound),
+    ] {
+        assert_code(
+            client
+                .get_payment(GetPaymentRequest {
+                    our_node_name: missing.to_string(),
+                    payment_hash: payment_hash.to_string(),
+                })
+                .await,
+            code,
+        );
+    }
+    for (direction, status, code) in [
+        (Some(7), None, Code::InvalidArgument),
+        (None, Some(-1), Code::InvalidArgument),
+        (Some(1), Some(2), Code::NotFound),
+    ] {
+        assert_code(
+            client
+                .list_payments(ListPaymentsRequest {
+                    our_node_name: missing.to_string(),
+                    direction,
+                    status,
+                    offset: 0,
+                    limit: 10,
+                    since: None,
+                    until: None,
+                })
+                .await,
+            code,
+        );
+    }
+    assert_code(
+        client
+            .open_channel(OpenChannelRequest {
+                our_node_name: missing.to_string(),
+                node_id: "not a pubkey".to_string(),
+                net_address: "not an address".to_string(),
+                channel_amount_sats: -1,
+                push_to_counterparty_msat: -1,
+                announce_channel: false,
+            })
+            .await,
+        Code::NotFound,
+    );
+    assert_code(
+        client
+            .connect_to_peer(ConnectToPeerRequest {
+                our_node_name: missing.to_string(),
+                node_id: "not a pubkey".to_string(),
+                net_address: "not an address".to_string(),
+            })
+            .await,
+        Code::NotFound,
+    );
+    assert_code(
+        client
+            .disconnect_peer(DisconnectPeerRequest {
+                our_node_name: missing.to_string(),
+                node_id: "not a pubkey".to_string(),
+            })
+            .await,
+        Code::NotFound,
+    );
+
+    assert_code(
+        client.get_esplora_address(node_name(missing)).await,
+        Code::NotFound,
+    );
+    assert_code(
+        client.get_net_address(node_name(missing)).await,
+        Code::NotFound,
+    );
+
+    let new_wallet =
+        |network: Network, wallet_name: &str, listening_address: &str, esplora_address: &str| {
+            NewWalletRequest {
+                network: network as i32,
+                wallet_name: wallet_name.to_string(),
+                listening_address: listening_address.to_string(),
+                esplora_address: esplora_address.to_string(),
+                allow_mainnet: false,
+                password: "password".to_string(),
+                passphrase: String::new(),
+            }
+        };
+    let esplora = "https://blockstream.info/testnet/api";
+    for request in [
+        new_wallet(Network::Testnet, "", "0.0.0.0:9735", esplora),
+        new_wallet(Network::Testnet, "../escape", "0.0.0.0:9735", esplora),
+        new_wallet(Network::Testnet, "malformed", "", esplora),
+        new_wallet(Network::Testnet, "malformed", "0.0.0.0:9735", ""),
+        new_wallet(
+            Network::Testnet,
+            "malformed",
+            "0.0.0.0:9735",
+            "blockstream.info",
+        ),
+        NewWalletRequest {
+            network: 42,
+            ..new_wallet(Network::Testnet, "malformed", "0.0.0.0:9735", esplora)
+        },
+        NewWalletRequest {
+            password: String::new(),
+            ..new_wallet(Network::Testnet, "malformed", "0.0.0.0:9735", esplora)
+        },
+    ] {
+        assert_code(client.new_wallet(request).await, Code::InvalidArgument);
+    }
+    assert_code(
+        client
+            .new_wallet(new_wallet(
+                Network::Bitcoin,
+                "mainnet",
+                "0.0.0.0:9735",
+                esplora,
+            ))
+            .await,
+        Code::FailedPrecondition,
+    );
+
+    let restore_wallet = |wallet_name: &str, mnemonic: &str| RestoreWalletRequest {
+        network: Network::Regtest as i32,
+        wallet_name: wallet_name.to_string(),
+        listening_address: "127.0.0.1:9735".to_string(),
+        esplora_address: "http://127.0.0.1:3002".to_string(),
+        allow_mainnet: false,
+        mnemonic: mnemonic.to_string(),
+        passphrase: "".to_string(),
+        password: "password".to_string(),
+    };
+    let abandon = "abandon ".repeat(11);
+    for mnemonic in [
+        "".to_string(),
+        "not a mnemonic".to_string(),
+        format!("{}abandon", abandon),
+        format!("{}about about", abandon),
+    ] {
+        assert_code(
+            client
+                .restore_wallet(restore_wallet("restored", &mnemonic))
+                .await,
+            Code::InvalidArgument,
+        );
+    }
+
+    let running = client.is_node_running(node_name(missing)).await.unwrap();
+    assert!(!running.into_inner().success);
+    assert!(server.is_alive());
+}
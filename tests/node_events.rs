@@ -0,0 +1,131 @@
+mod common;
+
+use common::fake_esplora;
+use ldk_node::bitcoin::{Network, OutPoint, Txid};
+use ldk_node::lightning::ln::{ChannelId, PaymentHash};
+use ldk_node::{Event, UserChannelId};
+use walletrpc_tonic::config::NodeDefaults;
+use walletrpc_tonic::lightning::{self, parse_public_key};
+use walletrpc_tonic::walletrpc::node_event;
+use walletrpc_tonic::walletrpc::*;
+use walletrpc_tonic::{paths, NodeManager};
+
+const NODE_ID: &str = "02eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619";
+
+fn mapped(event: Event) -> node_event::Event {
+    let event = NodeEvent::from(event);
+    // The journal numbers and stamps events, not the mapping.
+    assert_eq!((event.seq, event.timestamp), (0, 0));
+    event.event.unwrap()
+}
+
+#[test]
+fn node_event_mapping() {
+    let node_id = parse_public_key(NODE_ID).unwrap();
+    let payment_hash = PaymentHash([0x11; 32]);
+    let hash_hex = "11".repeat(32);
+
+    assert_eq!(
+        mapped(Event::PaymentReceived {
+            payment_hash,
+            amount_msat: 21_000,
+        }),
+        node_event::Event::PaymentReceived(PaymentReceivedEvent {
+            payment_hash: hash_hex.clone(),
+            amount_msat: 21_000,
+        })
+    );
+    assert_eq!(
+        mapped(Event::PaymentSuccessful { payment_hash }),
+        node_event::Event::PaymentSuccessful(PaymentSuccessfulEvent {
+            payment_hash: hash_hex.clone(),
+        })
+    );
+    assert_eq!(
+        mapped(Event::PaymentFailed { payment_hash }),
+        node_event::Event::PaymentFailed(PaymentFailedEvent {
+            payment_hash: hash_hex,
+        })
+    );
+    let txid: Txid = "22".repeat(32).parse().unwrap();
+    assert_eq!(
+        mapped(Event::ChannelPending {
+            channel_id: ChannelId([0x33; 32]),
+            user_channel_id: UserChannelId(42),
+            former_temporary_channel_id: ChannelId([0x44; 32]),
+            counterparty_node_id: node_id,
+            funding_txo: OutPoint { txid, vout: 1 },
+        }),
+        node_event::Event::ChannelPending(ChannelPendingEvent {
+            channel_id: "33".repeat(32),
+            user_channel_id: format!("{:032x}", 42),
+            former_temporary_channel_id: "44".repeat(32),
+            counterparty_node_id: NODE_ID.to_string(),
+            funding_txo: format!("{}:1", "22".repeat(32)),
+        })
+    );
+    assert_eq!(
+        mapped(Event::ChannelReady {
+            channel_id: ChannelId([0x33; 32]),
+            user_channel_id: UserChannelId(42),
+            counterparty_node_id: Some(node_id),
+        }),
+        node_event::Event::ChannelReady(ChannelReadyEvent {
+            channel_id: "33".repeat(32),
+            user_channel_id: format!("{:032x}", 42),
+            counterparty_node_id: NODE_ID.to_string(),
+        })
+    );
+    // An unknown counterparty is sent as an empty string.
+    assert_eq!(
+        mapped(Event::ChannelClosed {
+            channel_id: ChannelId([0x33; 32]),
+            user_channel_id: UserChannelId(42),
+            counterparty_node_id: None,
+        }),
+        node_event::Event::ChannelClosed(ChannelClosedEvent {
+            channel_id: "33".repeat(32),
+            user_channel_id: format!("{:032x}", 42),
+            counterparty_node_id: String::new(),
+        })
+    );
+}
+
+#[test]
+fn node_events_fan_out() {
+    let data_dir =
+        std::env::temp_dir().join(format!("walletrpc-test-events-{}", std::process::id()));
+    assert!(paths::set_project_base_dir(
+        data_dir.to_str().unwrap().to_string()
+    ));
+    let manager = NodeManager::new(NodeDefaults::default());
+    manager
+        .create_wallet(
+            Network::Regtest,
+            false,
+            "fanout",
+            "127.0.0.1:9742",
+            Some(&fake_esplora()),
+            "",
+            "password",
+        )
+        .unwrap();
+    manager.start_node("fanout").unwrap();
+
+    // Every subscriber of the node gets each event.
+    let mut first = manager.subscribe_events("fanout").unwrap();
+    let mut second = manager.subscribe_events("fanout").unwrap();
+    let event = NodeEvent::from(Event::PaymentSuccessful {
+        payment_hash: PaymentHash([0x11; 32]),
+    });
+    let storage_dir = data_dir.join("wallets/fanout/ldk-data");
+    lightning::event_sender(storage_dir.to_str().unwrap())
+        .unwrap()
+        .send(event.clone())
+        .unwrap();
+    assert_eq!(first.try_recv().unwrap(), event);
+    assert_eq!(second.try_recv().unwrap(), event);
+
+    manager.shutdown().unwrap();
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use common::fake_esplora;
+use ldk_node::bitcoin::Network;
+use walletrpc_tonic::config::NodeDefaults;
+use walletrpc_tonic::lightning::{LightningError, NodeState};
+use walletrpc_tonic::{paths, NodeManager};
+
+#[test]
+fn node_lifecycle() {
+    let data_dir =
+        std::env::temp_dir().join(format!("walletrpc-test-lifecycle-{}", std::process::id()));
+    assert!(paths::set_project_base_dir(
+        data_dir.to_str().unwrap().to_string()
+    ));
+    let manager = NodeManager::new(NodeDefaults::default());
+    manager
+        .create_wallet(
+            Network::Regtest,
+            false,
+            "frank",
+            "127.0.0.1:9743",
+            Some(&fake_esplora()),
+            "",
+            "password",
+        )
+        .unwrap();
+
+    // Starting a running node does nothing, the node keeps its id.
+    manager.start_node("frank").unwrap();
+    assert_eq!(manager.node_state("frank").unwrap(), NodeState::Running);
+    let node_id = manager.node_id("frank").unwrap();
+    manager.start_node("frank").unwrap();
+    assert_eq!(manager.node_id("frank").unwrap(), node_id);
+
+    // The store stays locked while the node runs.
+    let lock_file = data_dir.join("wallets/frank/ldk-data/node.lock");
+    let store_lock = std::fs::File::open(&lock_file).unwrap();
+    assert!(store_lock.try_lock().is_err());
+    assert!(matches!(
+        manager.delete_wallet("frank", true),
+        Err(LightningError::NodeRunning(_))
+    ));
+
+    // A restarted node runs as the same node.
+    manager.restart_node("frank").unwrap();
+    assert_eq!(manager.node_state("frank").unwrap(), NodeState::Running);
+    assert_eq!(manager.node_id("frank").unwrap(), node_id);
+
+    // A stopped node is gone until it is started again, and its store unlocked.
+    manager.stop_node("frank").unwrap();
+    assert_eq!(manager.node_state("frank").unwrap(), NodeState::Stopped);
+    assert!(matches!(
+        manager.node_id("frank"),
+        Err(LightningError::NodeNotFound(_))
+    ));
+    manager.stop_node("frank").unwrap();
+    store_lock.try_lock().unwrap();
+    drop(store_lock);
+
+    // Its funds when stopped tell whether the wallet can be deleted.
+    manager.delete_wallet("frank", false).unwrap();
+    assert!(manager.list_wallets().unwrap().is_empty());
+
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
@@ -0,0 +1,178 @@
+use ldk_node::bitcoin::Network;
+use walletrpc_tonic::config::NodeDefaults;
+use walletrpc_tonic::lightning::{LightningError, NodeState};
+use walletrpc_tonic::wallet::{Wallet, WalletError};
+use walletrpc_tonic::{paths, NodeManager};
+
+#[test]
+fn node_manager() {
+    let data_dir =
+        std::env::temp_dir().join(format!("walletrpc-test-manager-{}", std::process::id()));
+    assert!(paths::set_project_base_dir(
+        data_dir.to_str().unwrap().to_string()
+    ));
+    let mut defaults = NodeDefaults::default();
+    defaults.set_esplora_address(Network::Regtest, "http://127.0.0.1:3002".to_string());
+    let manager = NodeManager::new(defaults);
+
+    // Wallets are managed in-process, without a server.
+    let mnemonic = manager
+        .create_wallet(
+            Network::Regtest,
+            false,
+            "alice",
+            "127.0.0.1:9735",
+            None,
+            "",
+            "password",
+        )
+        .unwrap();
+    assert_eq!(mnemonic.word_count(), 12);
+    manager
+        .restore_wallet(
+            Network::Regtest,
+            false,
+            "bob",
+            "127.0.0.1:9736",
+            Some("http://127.0.0.1:3003"),
+            &mnemonic.phrase(),
+            "",
+            "password",
+        )
+        .unwrap();
+    assert_eq!(manager.list_wallets().unwrap(), vec!["alice", "bob"]);
+    let config = manager.wallet_config("alice").unwrap();
+    assert_eq!(config.get_esplora_address(), "http://127.0.0.1:3002");
+    assert_eq!(
+        manager.esplora_address("bob").unwrap(),
+        "http://127.0.0.1:3003"
+    );
+    assert!(matches!(
+        manager.create_wallet(
+            Network::Bitcoin,
+            false,
+            "carol",
+            "127.0.0.1:9737",
+            None,
+            "",
+            "password"
+        ),
+        Err(LightningError::Wallet(_))
+    ));
+
+    // Seeds are encrypted, a new manager has to unlock them before starting nodes.
+    let seed = Wallet::unlock("alice", "password")
+        .unwrap()
+        .as_bytes()
+        .to_vec();
+    assert_eq!(seed, mnemonic.to_seed("").as_bytes());
+    assert!(matches!(
+        Wallet::unlock("alice", "wrong"),
+        Err(WalletError::WrongPassword)
+    ));
+    let locked = NodeManager::new(NodeDefaults::default());
+    assert!(matches!(
+        locked.start_node("alice"),
+        Err(LightningError::WalletLocked(_))
+    ));
+    assert!(matches!(
+        locked.unlock_wallet("alice", "wrong"),
+        Err(LightningError::Wallet(WalletError::WrongPassword))
+    ));
+    locked.unlock_wallet("alice", "password").unwrap();
+
+    // The mnemonic is stored encrypted too, the BIP39 passphrase is not stored.
+    assert_eq!(
+        manager
+            .export_mnemonic("alice", "password")
+            .unwrap()
+            .phrase(),
+        mnemonic.phrase()
+    );
+    assert!(matches!(
+        manager.export_mnemonic("alice", "wrong"),
+        Err(LightningError::Wallet(WalletError::WrongPassword))
+    ));
+    let mnemonic = manager
+        .create_wallet(
+            Network::Regtest,
+            false,
+            "dave",
+            "127.0.0.1:9738",
+            None,
+            "extra words",
+            "password",
+        )
+        .unwrap();
+    assert_eq!(
+        Wallet::unlock("dave", "password").unwrap().as_bytes(),
+        mnemonic.to_seed("extra words").as_bytes()
+    );
+    manager
+        .change_password("dave", "password", "secret")
+        .unwrap();
+    assert_eq!(
+        manager.export_mnemonic("dave", "secret").unwrap().phrase(),
+        mnemonic.phrase()
+    );
+
+    // Seeds written before they were encrypted are encrypted on the first unlock, such
+    // wallets have no mnemonic stored.
+    let seed_file = data_dir.join("wallets/alice/seed");
+    std::fs::write(&seed_file, &seed).unwrap();
+    std::fs::remove_file(data_dir.join("wallets/alice/mnemonic")).unwrap();
+    assert!(matches!(
+        Wallet::unlock("alice", ""),
+        Err(WalletError::InvalidPassword)
+    ));
+    assert_eq!(
+        Wallet::unlock("alice", "legacy").unwrap().as_bytes(),
+        &seed[..]
+    );
+    assert_ne!(std::fs::read(&seed_file).unwrap(), seed);
+    assert_eq!(
+        Wallet::unlock("alice", "legacy").unwrap().as_bytes(),
+        &seed[..]
+    );
+    assert!(matches!(
+        Wallet::export_mnemonic("alice", "legacy"),
+        Err(WalletError::MnemonicNotStored(_))
+    ));
+
+    // Nodes that aren't started are reported as such.
+    assert!(!manager.is_node_running("alice").unwrap());
+    assert!(matches!(
+        manager.node_id("alice"),
+        Err(LightningError::NodeNotFound(_))
+    ));
+    assert!(manager.replay_events("alice", 0, 0).unwrap().is_empty());
+
+    // Stopping a stopped node does nothing, and a node failing to start, here for lack of
+    // an esplora server, is left stopped rather than half started.
+    manager.stop_node("dave").unwrap();
+    assert_eq!(manager.node_state("dave").unwrap(), NodeState::Stopped);
+    assert!(matches!(
+        manager.start_node("dave"),
+        Err(LightningError::Node(_))
+    ));
+    assert_eq!(manager.node_state("dave").unwrap(), NodeState::Stopped);
+    assert!(matches!(
+        manager.restart_node("dave"),
+        Err(LightningError::Node(_))
+    ));
+
+    // Another process running the node holds the lock of its store.
+    let store_lock = std::fs::File::open(data_dir.join("wallets/dave/ldk-data/node.lock")).unwrap();
+    store_lock.try_lock().unwrap();
+    assert!(matches!(
+        manager.start_node("dave"),
+        Err(LightningError::StoreLocked(_))
+    ));
+    drop(store_lock);
+
+    let archive_dir = manager.delete_wallet("bob", false).unwrap();
+    assert!(std::path::Path::new(&archive_dir).is_dir());
+    assert_eq!(manager.list_wallets().unwrap(), vec!["alice", "dave"]);
+
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use common::fake_esplora;
+use ldk_node::bitcoin::Network;
+use ldk_node::lightning::ln::PaymentHash;
+use prost::Message;
+use walletrpc_tonic::config::NodeDefaults;
+use walletrpc_tonic::lightning::bytes_to_hex;
+use walletrpc_tonic::walletrpc::*;
+use walletrpc_tonic::{paths, NodeManager};
+
+#[test]
+fn payment_history() {
+    let data_dir =
+        std::env::temp_dir().join(format!("walletrpc-test-payments-{}", std::process::id()));
+    assert!(paths::set_project_base_dir(
+        data_dir.to_str().unwrap().to_string()
+    ));
+    let manager = NodeManager::new(NodeDefaults::default());
+    manager
+        .create_wallet(
+            Network::Regtest,
+            false,
+            "payments",
+            "127.0.0.1:9744",
+            Some(&fake_esplora()),
+            "",
+            "password",
+        )
+        .unwrap();
+    manager.start_node("payments").unwrap();
+    let hashes: Vec<[u8; 32]> = (0..4)
+        .map(|_| {
+            let invoice = manager
+                .create_invoice("payments", 1000, "history", 3600)
+                .unwrap();
+            *invoice.payment_hash().as_ref()
+        })
+        .collect();
+
+    // The last events of the first three payments, journaled out of order.
+    let journal = data_dir.join("wallets/payments/ldk-data/events.journal");
+    let mut file = OpenOptions::new().append(true).open(journal).unwrap();
+    for (seq, (hash, timestamp)) in [(hashes[0], 300), (hashes[1], 100), (hashes[2], 200)]
+        .into_iter()
+        .enumerate()
+    {
+        let event = NodeEvent {
+            seq: seq as u64 + 1,
+            timestamp,
+            event: Some(node_event::Event::PaymentFailed(PaymentFailedEvent {
+                payment_hash: bytes_to_hex(&hash),
+            })),
+        };
+        file.write_all(&event.encode_length_delimited_to_vec())
+            .unwrap();
+    }
+
+    let list = |since, until, offset, limit| {
+        let (payments, total) = manager
+            .list_payments("payments", None, None, since, until, offset, limit)
+            .unwrap();
+        let payments: Vec<_> = payments
+            .into_iter()
+            .map(|p| (p.hash, p.timestamp))
+            .collect();
+        (payments, total)
+    };
+
+    // Oldest first, the pending payment without an event last.
+    assert_eq!(
+        list(None, None, 0, 0),
+        (
+            vec![
+                (hashes[1], Some(100)),
+                (hashes[2], Some(200)),
+                (hashes[0], Some(300)),
+                (hashes[3], None),
+            ],
+            4
+        )
+    );
+    assert_eq!(
+        list(None, None, 1, 2),
+        (vec![(hashes[2], Some(200)), (hashes[0], Some(300))], 4)
+    );
+    // `since` is inclusive and `until` exclusive, payments without a time are left out.
+    assert_eq!(
+        list(Some(200), None, 0, 0),
+        (vec![(hashes[2], Some(200)), (hashes[0], Some(300))], 2)
+    );
+    assert_eq!(
+        list(None, Some(200), 0, 0),
+        (vec![(hashes[1], Some(100))], 1)
+    );
+    assert_eq!(list(Some(301), None, 0, 0), (vec![], 0));
+
+    let payment = manager
+        .payment("payments", &PaymentHash(hashes[0]))
+        .unwrap();
+    assert_eq!(payment.timestamp, Some(300));
+
+    manager.shutdown().unwrap();
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use std::time::Duration;
+
+use common::walletrpc::*;
+use common::{assert_code, node_name, Server};
+use tonic::Code;
+
+fn new_wallet(wallet_name: &str, esplora_address: &str) -> NewWalletRequest {
+    NewWalletRequest {
+        network: Network::Regtest as i32,
+        wallet_name: wallet_name.to_string(),
+        listening_address: "127.0.0.1:9735".to_string(),
+        esplora_address: esplora_address.to_string(),
+        allow_mainnet: false,
+        password: "password".to_string(),
+        passphrase: String::new(),
+    }
+}
+
+async fn exits(server: &mut Server) -> bool {
+    for _ in 0..50 {
+        if !server.is_alive() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn server_config() {
+    let dir = std::env::temp_dir().join(format!("walletrpc-test-config-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let data_dir = dir.join("data");
+    let config_file = dir.join("walletrpc.toml");
+    std::fs::write(
+        &config_file,
+        format!(
+            r#"
+listen = ["[::1]:50051"]
+data_dir = "{}"
+log_level = "debug"
+
+[esplora_addresses]
+regtest = "http://127.0.0.1:3002"
+"#,
+            data_dir.display()
+        ),
+    )
+    .unwrap();
+    let config_path = config_file.to_str().unwrap();
+
+    // The config file moves every file to the data dir and sets the default esplora server.
+    {
+        let mut server =
+            Server::start_with_env("config-file", &[("WALLETRPC_CONFIG", config_path)]);
+        server.base_dir = data_dir.clone();
+        let mut client = server.connect().await;
+        client.new_wallet(new_wallet("defaults", "")).await.unwrap();
+        let config = client
+            .get_wallet_config(node_name("defaults"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(config.esplora_address, "http://127.0.0.1:3002");
+        client
+            .new_wallet(new_wallet("explicit", "http://127.0.0.1:3003"))
+            .await
+            .unwrap();
+        let config = client
+            .get_wallet_config(node_name("explicit"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(config.esplora_address, "http://127.0.0.1:3003");
+        assert!(data_dir.join("wallets/defaults").exists());
+        assert!(!server.home_dir.join(".bits-wallet/wallets").exists());
+    }
+
+    // Without a config file given, the one in the data dir is read.
+    {
+        std::fs::create_dir_all(&data_dir).unwrap();
+        std::fs::copy(&config_file, data_dir.join("walletrpc.toml")).unwrap();
+        let server = Server::start_with_env(
+            "config-data-dir",
+            &[("WALLETRPC_DATA_DIR", data_dir.to_str().unwrap())],
+        );
+        let mut client = server.connect().await;
+        client.new_wallet(new_wallet("found", "")).await.unwrap();
+        let config = client
+            .get_wallet_config(node_name("found"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(config.esplora_address, "http://127.0.0.1:3002");
+    }
+
+    // Without a default, new wallets must name their esplora server.
+    {
+        let server = Server::start("config-none");
+        let mut client = server.connect().await;
+        assert_code(
+            client.new_wallet(new_wallet("defaults", "")).await,
+            Code::InvalidArgument,
+        );
+    }
+
+    // Environment variables and flags take precedence over the file, invalid settings
+    // stop the server.
+    {
+        let mut server = Server::start_with_env(
+            "config-invalid",
+            &[
+                ("WALLETRPC_CONFIG", config_path),
+                ("WALLETRPC_LOG_LEVEL", "loud"),
+            ],
+        );
+        server.base_dir = data_dir.clone();
+        assert!(exits(&mut server).await);
+    }
+    {
+        let mut server = Server::start_with_env(
+            "config-missing",
+            &[(
+                "WALLETRPC_CONFIG",
+                dir.join("missing.toml").to_str().unwrap(),
+            )],
+        );
+        assert!(exits(&mut server).await);
+    }
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
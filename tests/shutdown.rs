@@ -0,0 +1,113 @@
+mod common;
+
+use std::net::TcpListener;
+use std::time::{Duration, Instant};
+
+use common::walletrpc::*;
+use common::{fake_esplora, node_name, Client, Server};
+
+/// Takes every request and never answers.
+fn dead_webhook() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let mut streams = Vec::new();
+        for stream in listener.incoming() {
+            streams.push(stream);
+        }
+    });
+    url
+}
+
+async fn new_wallet(client: &mut Client, wallet_name: &str, listening_address: &str) {
+    client
+        .new_wallet(NewWalletRequest {
+            network: Network::Regtest as i32,
+            wallet_name: wallet_name.to_string(),
+            listening_address: listening_address.to_string(),
+            esplora_address: fake_esplora(),
+            allow_mainnet: false,
+            password: "password".to_string(),
+            passphrase: String::new(),
+        })
+        .await
+        .unwrap();
+}
+
+fn webhook(wallet_name: &str, url: &str) -> WebhookRequest {
+    WebhookRequest {
+        wallet_name: wallet_name.to_string(),
+        url: url.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn shutdown() {
+    let mut server = Server::start_with_env(
+        "shutdown",
+        &[
+            ("WALLETRPC_UNIX_SOCKET", "true"),
+            ("WALLETRPC_LISTEN", "[::1]:50051"),
+            ("WALLETRPC_SHUTDOWN_TIMEOUT", "5"),
+        ],
+    );
+    let mut client = server.connect_unix().await;
+    new_wallet(&mut client, "erin", "127.0.0.1:9735").await;
+    // An unreachable webhook doesn't hold up stopping the node.
+    client
+        .add_webhook(webhook("erin", &dead_webhook()))
+        .await
+        .unwrap();
+    client.start_node(node_name("erin")).await.unwrap();
+
+    // SIGTERM stops the listeners and the running node, then the server exits cleanly and
+    // removes its socket.
+    let status = server
+        .terminate(Duration::from_secs(10))
+        .await
+        .expect("walletrpc-server did not exit");
+    assert!(status.success(), "{:?}", status);
+    assert!(!server.base_dir.join("walletrpc.sock").exists());
+    assert!(server.wallet_file("erin", "seed").exists());
+    let log =
+        std::fs::read_to_string(server.wallet_file("erin", "ldk-data/logs/ldk_node_latest.log"))
+            .unwrap();
+    assert!(log.contains("Shutdown complete."));
+    assert!(server
+        .wallet_file("erin", "ldk-data/events.journal")
+        .exists());
+}
+
+#[tokio::test]
+async fn shutdown_timeout() {
+    let mut server = Server::start_with_env(
+        "shutdown-timeout",
+        &[
+            ("WALLETRPC_UNIX_SOCKET", "true"),
+            ("WALLETRPC_LISTEN", "[::1]:50061"),
+            ("WALLETRPC_SHUTDOWN_TIMEOUT", "1"),
+        ],
+    );
+    let mut client = server.connect_unix().await;
+    new_wallet(&mut client, "fred", "127.0.0.1:9736").await;
+    let url = dead_webhook();
+    client.add_webhook(webhook("fred", &url)).await.unwrap();
+    client.start_node(node_name("fred")).await.unwrap();
+
+    // A test delivery to the dead webhook keeps retrying for close to a minute, which the
+    // server would wait for before it exits.
+    let mut pinging = client.clone();
+    let ping = tokio::spawn(async move { pinging.test_webhook(webhook("fred", &url)).await });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // The server gives up once the timeout is hit, and still removes its socket.
+    let started = Instant::now();
+    let status = server
+        .terminate(Duration::from_secs(10))
+        .await
+        .expect("walletrpc-server did not exit");
+    assert!(!status.success(), "{:?}", status);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(!server.base_dir.join("walletrpc.sock").exists());
+    ping.abort();
+}
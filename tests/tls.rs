@@ -0,0 +1,86 @@
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+
+use common::walletrpc::*;
+use common::{connect_with, Server};
+use rcgen::{BasicConstraints, Certificate, CertificateParams, IsCa};
+use tonic::transport::{self, ClientTlsConfig, Identity};
+
+fn ca() -> Certificate {
+    let mut params = CertificateParams::new(Vec::new());
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    Certificate::from_params(params).unwrap()
+}
+
+fn trusting(cert_pem: &str) -> ClientTlsConfig {
+    ClientTlsConfig::new()
+        .ca_certificate(transport::Certificate::from_pem(cert_pem))
+        .domain_name("localhost")
+}
+
+#[tokio::test]
+async fn tls() {
+    let dir = std::env::temp_dir().join(format!("walletrpc-test-tls-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let write = |file: &str, contents: &str| {
+        let path = dir.join(file);
+        std::fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_string()
+    };
+
+    // A certificate is generated on first run, its key only readable by the owner.
+    {
+        let server = Server::start("tls-generated");
+        let mut client = server.connect().await;
+        client.list_wallets(ListWalletsRequest {}).await.unwrap();
+        let key_file = server.home_dir.join(".bits-wallet/tls.key");
+        let mode = std::fs::metadata(key_file).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // A user-provided certificate is served instead.
+    {
+        let ca = ca();
+        let cert = Certificate::from_params(CertificateParams::new(vec!["localhost".to_string()]))
+            .unwrap();
+        let cert_pem = cert.serialize_pem_with_signer(&ca).unwrap();
+        let cert_path = write("server.cert", &cert_pem);
+        let key_path = write("server.key", &cert.serialize_private_key_pem());
+        let server = Server::start_with_env(
+            "tls-provided",
+            &[
+                ("WALLETRPC_TLS_CERT_PATH", &cert_path),
+                ("WALLETRPC_TLS_KEY_PATH", &key_path),
+            ],
+        );
+        let token = server.admin_token();
+        let mut client = connect_with(trusting(&ca.serialize_pem().unwrap()), Some(&token)).await;
+        client.list_wallets(ListWalletsRequest {}).await.unwrap();
+        assert!(!server.tls_cert().exists());
+    }
+
+    // With a client CA, only clients presenting a certificate it signed are served.
+    {
+        let ca = ca();
+        let ca_path = write("client-ca.cert", &ca.serialize_pem().unwrap());
+        let server =
+            Server::start_with_env("tls-mutual", &[("WALLETRPC_TLS_CLIENT_CA_PATH", &ca_path)]);
+        let mut client = server.connect().await;
+        assert!(client.list_wallets(ListWalletsRequest {}).await.is_err());
+
+        let server_cert = std::fs::read_to_string(server.tls_cert()).unwrap();
+        let client_cert =
+            Certificate::from_params(CertificateParams::new(vec!["client".to_string()])).unwrap();
+        let identity = Identity::from_pem(
+            client_cert.serialize_pem_with_signer(&ca).unwrap(),
+            client_cert.serialize_private_key_pem(),
+        );
+        let token = server.admin_token();
+        let mut client =
+            connect_with(trusting(&server_cert).identity(identity), Some(&token)).await;
+        client.list_wallets(ListWalletsRequest {}).await.unwrap();
+    }
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+
+use common::walletrpc::*;
+use common::Server;
+use tonic::transport::Endpoint;
+
+#[tokio::test]
+async fn unix_socket() {
+    // Only the socket is served unless listen addresses are given.
+    {
+        let server = Server::start_with_env("unix-only", &[("WALLETRPC_UNIX_SOCKET", "true")]);
+        let mut client = server.connect_unix().await;
+        client.list_wallets(ListWalletsRequest {}).await.unwrap();
+        let socket = server.base_dir.join("walletrpc.sock");
+        let mode = std::fs::metadata(socket).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let tcp = Endpoint::from_static("http://[::1]:50051");
+        assert!(tcp.connect().await.is_err());
+    }
+
+    // Alongside TCP, which still requires TLS.
+    {
+        let server = Server::start_with_env(
+            "unix-tcp",
+            &[
+                ("WALLETRPC_UNIX_SOCKET", "true"),
+                ("WALLETRPC_LISTEN", "[::1]:50051"),
+            ],
+        );
+        let mut client = server.connect_unix().await;
+        client.list_wallets(ListWalletsRequest {}).await.unwrap();
+        let mut client = server.connect().await;
+        client.list_wallets(ListWalletsRequest {}).await.unwrap();
+    }
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use std::time::Duration;
+
+use common::Server;
+use tonic::Code;
+use walletrpc_tonic::wallet_client::{ChannelInfo, ClientConfig, PaymentInfo, WalletClient};
+use walletrpc_tonic::walletrpc;
+
+const NODE_ID: &str = "02eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619";
+
+#[tokio::test]
+async fn wallet_client() {
+    // Idempotent calls are retried until the server comes up, other calls fail right away.
+    {
+        let data_dir = std::env::temp_dir().join(format!(
+            "walletrpc-test-client-retry-{}",
+            std::process::id()
+        ));
+        let socket = data_dir.join("walletrpc.sock");
+        let config = ClientConfig::new(format!("unix:{}", socket.display()))
+            .retries(50, Duration::from_millis(20));
+        let client = WalletClient::connect_lazy(config).unwrap();
+        let e = client.get_on_chain_address("alice").await.unwrap_err();
+        assert_eq!(e.code(), Code::Unavailable);
+
+        let list_wallets = tokio::spawn(async move { client.list_wallets().await });
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let data_dir = data_dir.to_str().unwrap();
+        let _server = Server::start_with_env(
+            "client-retry",
+            &[
+                ("WALLETRPC_DATA_DIR", data_dir),
+                ("WALLETRPC_UNIX_SOCKET", "true"),
+            ],
+        );
+        // the server is reached, it then refuses the call for the missing token
+        let e = list_wallets.await.unwrap().unwrap_err();
+        assert_eq!(e.code(), Code::Unauthenticated);
+        assert_eq!(e.kind(), "MissingToken");
+    }
+
+    // Over TLS with a token.
+    {
+        let server = Server::start("client-tls");
+        server.connect().await;
+        let config = ClientConfig::new("https://[::1]:50051")
+            .tls_ca_certificate(std::fs::read(server.tls_cert()).unwrap())
+            .tls_domain("localhost")
+            .token(server.admin_token());
+        let client = WalletClient::connect(config).await.unwrap();
+        assert!(client.list_wallets().await.unwrap().is_empty());
+        client
+            .raw()
+            .new_wallet(walletrpc::NewWalletRequest {
+                network: walletrpc::Network::Regtest as i32,
+                wallet_name: "alice".to_string(),
+                listening_address: "127.0.0.1:9735".to_string(),
+                esplora_address: "http://127.0.0.1:3002".to_string(),
+                allow_mainnet: false,
+                password: "password".to_string(),
+                passphrase: String::new(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(client.list_wallets().await.unwrap(), vec!["alice"]);
+        let config = client.get_wallet_config("alice").await.unwrap();
+        assert_eq!(config.esplora_address, "http://127.0.0.1:3002");
+        assert!(!client.is_node_running("alice").await.unwrap());
+        let e = client.get_node_id("alice").await.unwrap_err();
+        assert_eq!(e.code(), Code::NotFound);
+        assert_eq!(e.kind(), "NodeNotFound");
+    }
+
+    // https servers need their certificate.
+    let e = WalletClient::connect_lazy(ClientConfig::new("https://[::1]:50051"))
+        .err()
+        .unwrap();
+    assert_eq!(e.kind(), "MissingCertificate");
+
+    // Replies are parsed into the bitcoin and lightning types.
+    let channel = ChannelInfo::try_from(walletrpc::Channel {
+        channel_id: "11".repeat(32),
+        counterparty_node_id: NODE_ID.to_string(),
+        channel_value_sats: 100_000,
+        balance_msat: 40_000_000,
+        outbound_capacity_msat: 30_000_000,
+        inbound_capacity_msat: 60_000_000,
+        is_outbound: true,
+        is_channel_ready: true,
+        is_usable: true,
+        is_public: false,
+        user_channel_id: format!("{:032x}", 42),
+    })
+    .unwrap();
+    assert_eq!(channel.channel_id.0, [0x11; 32]);
+    assert_eq!(channel.user_channel_id, 42);
+    assert_eq!(channel.counterparty_node_id.to_string(), NODE_ID);
+    assert_eq!(channel.channel_value.to_sat(), 100_000);
+    let payment = PaymentInfo::try_from(walletrpc::Payment {
+        hash: "22".repeat(32),
+        preimage: String::new(),
+        secret: "33".repeat(32),
+        amount_msat: Some(1000),
+        direction: walletrpc::PaymentDirection::Inbound as i32,
+        status: walletrpc::PaymentStatus::Pending as i32,
+        timestamp: None,
+    })
+    .unwrap();
+    assert_eq!(payment.hash.0, [0x22; 32]);
+    assert_eq!(payment.preimage, None);
+    assert_eq!(payment.secret.unwrap().0, [0x33; 32]);
+    assert_eq!(payment.direction, walletrpc::PaymentDirection::Inbound);
+    let e = PaymentInfo::try_from(walletrpc::Payment {
+        hash: "not hex".to_string(),
+        ..Default::default()
+    })
+    .unwrap_err();
+    assert_eq!(e.kind(), "InvalidReply");
+}
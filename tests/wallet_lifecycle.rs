@@ -0,0 +1,331 @@
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+
+use common::walletrpc::*;
+use common::{assert_code, fake_esplora, node_name, Server};
+use tonic::Code;
+
+#[tokio::test]
+async fn wallet_lifecycle() {
+    let server = Server::start("lifecycle");
+    let mut client = server.connect().await;
+    let missing = "no-such-wallet";
+    let new_wallet = |network: Network,
+                      wallet_name: &str,
+                      listening_address: &str,
+                      esplora_address: &str| NewWalletRequest {
+        network: network as i32,
+        wallet_name: wallet_name.to_string(),
+        listening_address: listening_address.to_string(),
+        esplora_address: esplora_address.to_string(),
+        allow_mainnet: false,
+        password: "password".to_string(),
+        passphrase: String::new(),
+    };
+    let esplora = "https://blockstream.info/testnet/api";
+
+    let reply = client
+        .new_wallet(new_wallet(
+            Network::Regtest,
+            "regtest",
+            "127.0.0.1:9735",
+            "http://127.0.0.1:3002",
+        ))
+        .await
+        .unwrap();
+    assert_eq!(reply.into_inner().mnemonic.split_whitespace().count(), 12);
+    let config = server.wallet_file("regtest", "config.json");
+    let config = std::fs::read_to_string(config).unwrap();
+    assert!(config.contains("\"regtest\""), "{}", config);
+    let address = client.get_net_address(node_name("regtest")).await.unwrap();
+    assert_eq!(address.into_inner().address, "127.0.0.1:9735");
+    let address = client
+        .get_esplora_address(node_name("regtest"))
+        .await
+        .unwrap();
+    assert_eq!(address.into_inner().address, "http://127.0.0.1:3002");
+
+    // An existing wallet, and its seed, is never overwritten.
+    let seed_file = server.wallet_file("regtest", "seed");
+    let seed = std::fs::read(&seed_file).unwrap();
+    assert_code(
+        client
+            .new_wallet(new_wallet(
+                Network::Regtest,
+                "regtest",
+                "127.0.0.1:9736",
+                esplora,
+            ))
+            .await,
+        Code::AlreadyExists,
+    );
+    assert_eq!(std::fs::read(&seed_file).unwrap(), seed);
+
+    let restore_wallet = |wallet_name: &str, mnemonic: &str| RestoreWalletRequest {
+        network: Network::Regtest as i32,
+        wallet_name: wallet_name.to_string(),
+        listening_address: "127.0.0.1:19735".to_string(),
+        esplora_address: "http://127.0.0.1:3002".to_string(),
+        allow_mainnet: false,
+        mnemonic: mnemonic.to_string(),
+        passphrase: "".to_string(),
+        password: "password".to_string(),
+    };
+    let abandon = "abandon ".repeat(11);
+    let mnemonic = format!("{}about", abandon);
+    assert_code(
+        client
+            .restore_wallet(restore_wallet("regtest", &mnemonic))
+            .await,
+        Code::AlreadyExists,
+    );
+    assert_eq!(std::fs::read(&seed_file).unwrap(), seed);
+
+    // Without an esplora server the restored node can't start, but the wallet is restored.
+    let restored = client
+        .restore_wallet(restore_wallet("restored", &mnemonic))
+        .await;
+    let status = restored.as_ref().unwrap_err();
+    assert!(status.message().starts_with("Wallet restored was restored"));
+    assert_code(restored, Code::Unavailable);
+    assert_code(
+        client.start_node(node_name("restored")).await,
+        Code::Unavailable,
+    );
+    // The failed start leaves the node stopped, stopping it again does nothing.
+    let running = client.is_node_running(node_name("restored")).await.unwrap();
+    assert!(!running.into_inner().success);
+    client.stop_node(node_name("restored")).await.unwrap();
+    assert_code(
+        client.restart_node(node_name("restored")).await,
+        Code::Unavailable,
+    );
+
+    // Otherwise the restored node is started and its id returned.
+    let node_id = client
+        .restore_wallet(RestoreWalletRequest {
+            listening_address: "127.0.0.1:19736".to_string(),
+            esplora_address: fake_esplora(),
+            ..restore_wallet("running", &mnemonic)
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .node_id;
+    let running_node_id = client.get_node_id(node_name("running")).await.unwrap();
+    assert_eq!(running_node_id.into_inner().node_id, node_id);
+    client.stop_node(node_name("running")).await.unwrap();
+    client
+        .delete_wallet(DeleteWalletRequest {
+            wallet_name: "running".to_string(),
+            force: false,
+        })
+        .await
+        .unwrap();
+
+    // The seed is only kept encrypted, readable by the owner alone.
+    let restored_seed = std::fs::read(server.wallet_file("restored", "seed")).unwrap();
+    let restored_seed = String::from_utf8(restored_seed).unwrap();
+    // BIP39 test vector for "abandon ... about" without a passphrase
+    assert!(!restored_seed.contains("5eb00bbddcf069084889a8ab9155568165f5c453"));
+    let mode = |path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode(server.wallet_file("restored", "seed")), 0o600);
+    assert_eq!(mode(server.wallet_file("restored", "mnemonic")), 0o600);
+    assert_eq!(mode(server.wallet_file("restored", "config.json")), 0o600);
+    assert_eq!(mode(server.wallet_file("restored", "ldk-data")), 0o700);
+    assert_eq!(mode(server.wallet_file("restored", "")), 0o700);
+
+    let unlock_wallet = |wallet_name: &str, password: &str| UnlockWalletRequest {
+        wallet_name: wallet_name.to_string(),
+        password: password.to_string(),
+    };
+    let change_password = |old_password: &str, new_password: &str| ChangePasswordRequest {
+        wallet_name: "restored".to_string(),
+        old_password: old_password.to_string(),
+        new_password: new_password.to_string(),
+    };
+    assert_code(
+        client
+            .unlock_wallet(unlock_wallet("restored", "wrong"))
+            .await,
+        Code::PermissionDenied,
+    );
+    assert_code(
+        client
+            .unlock_wallet(unlock_wallet(missing, "password"))
+            .await,
+        Code::NotFound,
+    );
+    client
+        .unlock_wallet(unlock_wallet("restored", "password"))
+        .await
+        .unwrap();
+    assert_code(
+        client
+            .change_password(change_password("wrong", "secret"))
+            .await,
+        Code::PermissionDenied,
+    );
+    assert_code(
+        client
+            .change_password(change_password("password", ""))
+            .await,
+        Code::InvalidArgument,
+    );
+    client
+        .change_password(change_password("password", "secret"))
+        .await
+        .unwrap();
+    assert_code(
+        client
+            .unlock_wallet(unlock_wallet("restored", "password"))
+            .await,
+        Code::PermissionDenied,
+    );
+    client
+        .unlock_wallet(unlock_wallet("restored", "secret"))
+        .await
+        .unwrap();
+
+    // The mnemonic is exported with the wallet password.
+    let export_mnemonic = |password: &str| ExportMnemonicRequest {
+        wallet_name: "restored".to_string(),
+        password: password.to_string(),
+    };
+    assert_code(
+        client.export_mnemonic(export_mnemonic("password")).await,
+        Code::PermissionDenied,
+    );
+    let reply = client
+        .export_mnemonic(export_mnemonic("secret"))
+        .await
+        .unwrap();
+    assert_eq!(reply.into_inner().mnemonic, mnemonic);
+
+    let wallets = client.list_wallets(ListWalletsRequest {}).await.unwrap();
+    assert_eq!(
+        wallets.into_inner().wallet_names,
+        vec!["regtest", "restored"]
+    );
+    let config = client
+        .get_wallet_config(node_name("regtest"))
+        .await
+        .unwrap();
+    let config = config.into_inner();
+    assert_eq!(config.network, Network::Regtest as i32);
+    assert_eq!(config.event_retention_days, 30);
+    assert_code(
+        client.get_wallet_config(node_name(missing)).await,
+        Code::NotFound,
+    );
+
+    let update_wallet_config =
+        |wallet_name: &str, listening_address: &str| UpdateWalletConfigRequest {
+            wallet_name: wallet_name.to_string(),
+            listening_address: listening_address.to_string(),
+            esplora_address: "http://127.0.0.1:3003".to_string(),
+            event_retention_days: Some(7),
+        };
+    assert_code(
+        client
+            .update_wallet_config(update_wallet_config("regtest", ""))
+            .await,
+        Code::InvalidArgument,
+    );
+    assert_code(
+        client
+            .update_wallet_config(update_wallet_config(missing, "127.0.0.1:9737"))
+            .await,
+        Code::NotFound,
+    );
+    // Names reaching out of the wallets dir are refused, even where the config exists.
+    let outside = "../wallets/regtest";
+    assert_code(
+        client.get_wallet_config(node_name(outside)).await,
+        Code::InvalidArgument,
+    );
+    assert_code(
+        client
+            .update_wallet_config(update_wallet_config(outside, "127.0.0.1:9737"))
+            .await,
+        Code::InvalidArgument,
+    );
+    assert_code(
+        client
+            .add_webhook(WebhookRequest {
+                wallet_name: outside.to_string(),
+                url: "http://127.0.0.1:8080/hook".to_string(),
+            })
+            .await,
+        Code::InvalidArgument,
+    );
+    client
+        .update_wallet_config(update_wallet_config("regtest", "127.0.0.1:9737"))
+        .await
+        .unwrap();
+    let config = client
+        .get_wallet_config(node_name("regtest"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(config.listening_address, "127.0.0.1:9737");
+    assert_eq!(config.esplora_address, "http://127.0.0.1:3003");
+    assert_eq!(config.event_retention_days, 7);
+
+    // Addresses are checked against the network of the wallet before the node is needed.
+    let send_to_address = |address: &str, fee_rate_sat_per_vb| SendToAddressRequest {
+        our_node_name: "regtest".to_string(),
+        address: address.to_string(),
+        amount_sats: 10_000,
+        fee_rate_sat_per_vb,
+    };
+    let mainnet_address = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+    let testnet_address = "mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn";
+    for (address, fee_rate, code) in [
+        ("not an address", None, Code::InvalidArgument),
+        (mainnet_address, None, Code::InvalidArgument),
+        (testnet_address, Some(5), Code::Unimplemented),
+        (testnet_address, None, Code::NotFound),
+    ] {
+        assert_code(
+            client
+                .send_to_address(send_to_address(address, fee_rate))
+                .await,
+            code,
+        );
+    }
+    let sweep_all = |address: &str| SweepAllRequest {
+        our_node_name: "regtest".to_string(),
+        address: address.to_string(),
+    };
+    assert_code(
+        client.sweep_all(sweep_all(mainnet_address)).await,
+        Code::InvalidArgument,
+    );
+    assert_code(
+        client.sweep_all(sweep_all(testnet_address)).await,
+        Code::NotFound,
+    );
+
+    let delete_wallet = |wallet_name: &str| DeleteWalletRequest {
+        wallet_name: wallet_name.to_string(),
+        force: false,
+    };
+    assert_code(
+        client.delete_wallet(delete_wallet(missing)).await,
+        Code::NotFound,
+    );
+    assert_code(
+        client.delete_wallet(delete_wallet("../wallets")).await,
+        Code::InvalidArgument,
+    );
+    let reply = client
+        .delete_wallet(delete_wallet("regtest"))
+        .await
+        .unwrap();
+    let archived_seed = format!("{}/seed", reply.into_inner().archive_dir);
+    assert_eq!(std::fs::read(archived_seed).unwrap(), seed);
+    let wallets = client.list_wallets(ListWalletsRequest {}).await.unwrap();
+    assert_eq!(wallets.into_inner().wallet_names, vec!["restored"]);
+}
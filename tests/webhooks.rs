@@ -0,0 +1,210 @@
+mod common;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use common::walletrpc::*;
+use common::{assert_code, node_name, Server};
+use ldk_node::bitcoin::hashes::hmac::{Hmac, HmacEngine};
+use ldk_node::bitcoin::hashes::{sha256, Hash, HashEngine};
+use tonic::Code;
+use walletrpc_tonic::wallet::Webhook;
+use walletrpc_tonic::walletrpc::NodeEvent;
+use walletrpc_tonic::webhook;
+
+struct Delivery {
+    signature: String,
+    body: String,
+}
+
+/// Answers each request with the next status, recording what was delivered.
+fn receiver(statuses: Vec<u16>) -> (String, mpsc::Receiver<Delivery>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let (sender, deliveries) = mpsc::channel();
+    std::thread::spawn(move || {
+        for status in statuses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut content_length = 0;
+            let mut signature = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                let (name, value) = line.split_once(": ").unwrap_or((line, ""));
+                match name.to_lowercase().as_str() {
+                    "content-length" => content_length = value.parse().unwrap(),
+                    "x-walletrpc-signature" => signature = value.to_string(),
+                    _ => {}
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            let response = format!(
+                "HTTP/1.1 {} Status\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                status
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+            sender
+                .send(Delivery {
+                    signature,
+                    body: String::from_utf8(body).unwrap(),
+                })
+                .unwrap();
+        }
+    });
+    (url, deliveries)
+}
+
+fn sign(secret: &str, body: &str) -> String {
+    let mut engine = HmacEngine::<sha256::Hash>::new(secret.as_bytes());
+    engine.input(body.as_bytes());
+    format!("sha256={}", Hmac::<sha256::Hash>::from_engine(engine))
+}
+
+#[tokio::test]
+async fn webhooks() {
+    let server = Server::start("webhooks");
+    let mut client = server.connect().await;
+
+    client
+        .new_wallet(NewWalletRequest {
+            network: Network::Regtest as i32,
+            wallet_name: "hooks".to_string(),
+            listening_address: "127.0.0.1:9735".to_string(),
+            esplora_address: "http://127.0.0.1:3002".to_string(),
+            allow_mainnet: false,
+            password: "password".to_string(),
+            passphrase: String::new(),
+        })
+        .await
+        .unwrap();
+    let webhook = |wallet_name: &str, url: &str| WebhookRequest {
+        wallet_name: wallet_name.to_string(),
+        url: url.to_string(),
+    };
+
+    // The first delivery fails and is retried.
+    let (url, deliveries) = receiver(vec![500, 200]);
+    assert_code(
+        client
+            .add_webhook(webhook("hooks", "ftp://example.com"))
+            .await,
+        Code::InvalidArgument,
+    );
+    assert_code(
+        client.add_webhook(webhook("no-such-wallet", &url)).await,
+        Code::NotFound,
+    );
+    let secret = client
+        .add_webhook(webhook("hooks", &url))
+        .await
+        .unwrap()
+        .into_inner()
+        .secret;
+    assert_eq!(secret.len(), 64);
+    assert_code(
+        client.add_webhook(webhook("hooks", &url)).await,
+        Code::AlreadyExists,
+    );
+    let config = client
+        .get_wallet_config(node_name("hooks"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(config.webhook_urls, vec![url.clone()]);
+
+    client.test_webhook(webhook("hooks", &url)).await.unwrap();
+    for _ in 0..2 {
+        let delivery = deliveries.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(delivery.signature, sign(&secret, &delivery.body));
+        let payload: serde_json::Value = serde_json::from_str(&delivery.body).unwrap();
+        assert_eq!(payload["wallet_name"], "hooks");
+        assert_eq!(payload["seq"], 0);
+    }
+
+    client.remove_webhook(webhook("hooks", &url)).await.unwrap();
+    assert_code(
+        client.remove_webhook(webhook("hooks", &url)).await,
+        Code::NotFound,
+    );
+    assert_code(
+        client.test_webhook(webhook("hooks", &url)).await,
+        Code::NotFound,
+    );
+
+    // Every attempt fails, the ping ends up in the dead-letter log.
+    let (url, _deliveries) = receiver(vec![503; 5]);
+    client.add_webhook(webhook("hooks", &url)).await.unwrap();
+    assert_code(
+        client.test_webhook(webhook("hooks", &url)).await,
+        Code::Unavailable,
+    );
+    let dead_letters =
+        std::fs::read_to_string(server.wallet_file("hooks", "ldk-data/webhooks.dead-letter.jsonl"))
+            .unwrap();
+    let dead_letter: serde_json::Value = serde_json::from_str(dead_letters.trim()).unwrap();
+    assert_eq!(dead_letter["url"], url);
+    assert_eq!(dead_letter["attempts"], 5);
+}
+
+#[test]
+fn stopping_dead_letters_pending_deliveries() {
+    let ldk_data_dir =
+        std::env::temp_dir().join(format!("walletrpc-test-hook-stop-{}", std::process::id()));
+    std::fs::create_dir_all(&ldk_data_dir).unwrap();
+    // Takes every request and never answers.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let mut streams = Vec::new();
+        for stream in listener.incoming() {
+            streams.push(stream);
+        }
+    });
+
+    let deliveries = webhook::spawn(
+        ldk_data_dir.to_str().unwrap().to_string(),
+        "hooks".to_string(),
+        vec![Webhook {
+            url: url.clone(),
+            secret: "secret".to_string(),
+        }],
+    )
+    .unwrap();
+    for seq in 1..=3 {
+        deliveries.send(&NodeEvent {
+            seq,
+            ..Default::default()
+        });
+    }
+    std::thread::sleep(Duration::from_millis(200));
+
+    // Stopping doesn't wait out the attempt in flight nor retry, and nothing is lost.
+    let started = Instant::now();
+    deliveries.stop();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    let dead_letters =
+        std::fs::read_to_string(ldk_data_dir.join("webhooks.dead-letter.jsonl")).unwrap();
+    let mut seqs: Vec<u64> = dead_letters
+        .lines()
+        .map(|line| {
+            let dead_letter: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(dead_letter["url"], url);
+            assert_eq!(dead_letter["attempts"], 0);
+            let payload: serde_json::Value =
+                serde_json::from_str(dead_letter["payload"].as_str().unwrap()).unwrap();
+            payload["seq"].as_u64().unwrap()
+        })
+        .collect();
+    seqs.sort();
+    assert_eq!(seqs, vec![1, 2, 3]);
+
+    std::fs::remove_dir_all(ldk_data_dir).unwrap();
+}